@@ -0,0 +1,271 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::{
+    report::Format,
+    types::{Difficulty, MIN_CUSTOM_SIZE},
+};
+
+pub const HELP: &str = "\
+termsweeper, Minesweeper in the terminal
+
+Usage: termsweeper [COMMAND] [OPTIONS]
+
+Commands:
+  leaderboard           Print the leaderboard, --difficulty picks one difficulty
+  stats                 Print your stats, --difficulty picks one difficulty
+  write-config          Write a commented default config file
+
+Options:
+  --difficulty <LEVEL>  Start on easy, medium, hard or custom
+  --width <N>           Width of a custom board
+  --height <N>          Height of a custom board
+  --mines <N>           Number of mines on a custom board
+  --seed <N>            Start with the board dealt by this seed
+  --no-guess            Only deal boards that never need a guess
+  --ascii               Draw the board with plain ASCII characters
+  --config <PATH>       Read settings from this file instead of the usual config
+  --data-dir <PATH>     Keep scores, stats and saves in this folder
+  --format <FORMAT>     Print leaderboard and stats as a table, json or csv
+  -h, --help            Show this help
+  -V, --version         Show the version
+
+Setting TERMSWEEPER_DIR keeps every file, config included, in that folder.
+";
+
+/// What the program was asked to do
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Play,
+    Leaderboard,
+    Stats,
+    WriteConfig,
+    Help,
+    Version,
+}
+
+impl Command {
+    fn parse(str: &str) -> Option<Self> {
+        match str {
+            "leaderboard" => Some(Self::Leaderboard),
+            "stats" => Some(Self::Stats),
+            "write-config" => Some(Self::WriteConfig),
+            _ => None,
+        }
+    }
+}
+
+/// Command line arguments, anything left out falls back to the config
+pub struct Cli {
+    pub command: Command,
+    pub difficulty: Option<Difficulty>,
+    pub seed: Option<u64>,
+    pub no_guess: bool,
+    pub ascii: bool,
+    pub config: Option<PathBuf>,
+    pub data_dir: Option<PathBuf>,
+    pub format: Format,
+}
+
+impl Cli {
+    /// Reads the arguments the program was started with, not counting the program itself
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut command = None;
+        let (mut level, mut width, mut height, mut mines) = (None, None, None, None);
+        let mut cli = Self {
+            command: Command::Play,
+            difficulty: None,
+            seed: None,
+            no_guess: false,
+            ascii: false,
+            config: None,
+            data_dir: None,
+            format: Format::Table,
+        };
+        let mut format = None;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            // Values can be given as `--flag value` or `--flag=value`
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => {
+                    (flag.to_string(), Some(value.to_string()))
+                }
+                _ => (arg.clone(), None),
+            };
+            let mut value = || {
+                inline
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| anyhow!("{flag} needs a value"))
+            };
+            match flag.as_str() {
+                "-h" | "--help" => return Ok(Self::only(Command::Help)),
+                "-V" | "--version" => return Ok(Self::only(Command::Version)),
+                "--difficulty" => level = Some(value()?),
+                "--width" => width = Some(parse_number(&flag, &value()?)?),
+                "--height" => height = Some(parse_number(&flag, &value()?)?),
+                "--mines" => mines = Some(parse_number(&flag, &value()?)?),
+                "--seed" => cli.seed = Some(parse_number(&flag, &value()?)?),
+                "--config" => cli.config = Some(PathBuf::from(value()?)),
+                "--data-dir" => cli.data_dir = Some(PathBuf::from(value()?)),
+                "--format" => {
+                    let value = value()?;
+                    format = Some(Format::parse(&value).ok_or_else(|| {
+                        anyhow!("Unknown format '{value}', pick table, json or csv")
+                    })?);
+                }
+                "--no-guess" if inline.is_none() => cli.no_guess = true,
+                "--ascii" if inline.is_none() => cli.ascii = true,
+                _ if flag.starts_with('-') => bail!("Unknown option '{arg}', see --help"),
+                _ => match Command::parse(&arg) {
+                    Some(parsed) if command.is_none() => command = Some(parsed),
+                    _ => bail!("Unexpected argument '{arg}', see --help"),
+                },
+            }
+        }
+
+        cli.command = command.unwrap_or(Command::Play);
+        cli.difficulty = parse_difficulty(level.as_deref(), width, height, mines)?;
+        let prints = matches!(cli.command, Command::Leaderboard | Command::Stats);
+        if cli.command != Command::Play && (cli.seed.is_some() || cli.no_guess || cli.ascii) {
+            bail!("--seed, --no-guess and --ascii only apply when playing");
+        }
+        if !prints && cli.command != Command::Play && cli.difficulty.is_some() {
+            bail!("--difficulty only applies when playing or printing scores");
+        }
+        if let Some(format) = format {
+            if !prints {
+                bail!("--format only applies to the leaderboard and stats commands");
+            }
+            cli.format = format;
+        }
+        Ok(cli)
+    }
+
+    const fn only(command: Command) -> Self {
+        Self {
+            command,
+            difficulty: None,
+            seed: None,
+            no_guess: false,
+            ascii: false,
+            config: None,
+            data_dir: None,
+            format: Format::Table,
+        }
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T> {
+    value
+        .parse()
+        .map_err(|_| anyhow!("{flag} expects a number but got '{value}'"))
+}
+
+/// Works out the difficulty from `--difficulty` and the custom board options, giving the size of
+/// a board on its own implies a custom board
+fn parse_difficulty(
+    level: Option<&str>,
+    width: Option<usize>,
+    height: Option<usize>,
+    mines: Option<usize>,
+) -> Result<Option<Difficulty>> {
+    let custom_given = width.is_some() || height.is_some() || mines.is_some();
+    let level = level.map(str::to_lowercase);
+    let difficulty = match level.as_deref() {
+        None if !custom_given => return Ok(None),
+        Some("easy") => Difficulty::Easy,
+        Some("medium") => Difficulty::Medium,
+        Some("hard") => Difficulty::Hard,
+        Some("custom") | None => {
+            let missing: Vec<&str> = [("--width", width), ("--height", height), ("--mines", mines)]
+                .into_iter()
+                .filter(|(_, value)| value.is_none())
+                .map(|(flag, _)| flag)
+                .collect();
+            let (Some(width), Some(height), Some(mines)) = (width, height, mines) else {
+                bail!("A custom board also needs {}", missing.join(" and "));
+            };
+            if width < MIN_CUSTOM_SIZE || height < MIN_CUSTOM_SIZE {
+                bail!("Custom boards must be at least {MIN_CUSTOM_SIZE}x{MIN_CUSTOM_SIZE}");
+            }
+            let max_mines = Difficulty::max_mines(width, height)?;
+            if mines == 0 || mines > max_mines {
+                bail!("A {width}x{height} board holds between 1 and {max_mines} mines");
+            }
+            return Ok(Some(Difficulty::Custom {
+                width,
+                height,
+                mines,
+            }));
+        }
+        Some(level) => bail!("Unknown difficulty '{level}', pick easy, medium, hard or custom"),
+    };
+    if custom_given {
+        bail!("--width, --height and --mines only work with a custom difficulty");
+    }
+    Ok(Some(difficulty))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error(args: &str) -> String {
+        Cli::parse(args.split_whitespace().map(String::from))
+            .err()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn custom_boards_are_read() {
+        let cli = Cli::parse(["--width=9", "--height", "5", "--mines", "44"].map(String::from));
+        assert!(
+            cli.unwrap().difficulty
+                == Some(Difficulty::Custom {
+                    width: 9,
+                    height: 5,
+                    mines: 44
+                })
+        );
+    }
+
+    #[test]
+    fn overflowing_sizes_are_rejected() {
+        assert_eq!(
+            parse_error("--width 9223372036854775807 --height 4 --mines 1"),
+            "A 9223372036854775807x4 board is too big"
+        );
+        assert_eq!(
+            parse_error(&format!("--width {0} --height {0} --mines 1", usize::MAX)),
+            format!("A {0}x{0} board is too big", usize::MAX)
+        );
+        assert_eq!(
+            parse_error("--width 2000 --height 2000 --mines 1"),
+            "A 2000x2000 board is too big"
+        );
+    }
+
+    #[test]
+    fn zero_sizes_and_mines_are_rejected() {
+        assert_eq!(
+            parse_error("--width 0 --height 4 --mines 1"),
+            "Custom boards must be at least 4x4"
+        );
+        assert_eq!(
+            parse_error("--width 4 --height 0 --mines 1"),
+            "Custom boards must be at least 4x4"
+        );
+        assert_eq!(
+            parse_error("--width 4 --height 4 --mines 0"),
+            "A 4x4 board holds between 1 and 15 mines"
+        );
+        assert_eq!(
+            parse_error("--width 4 --height 4 --mines 16"),
+            "A 4x4 board holds between 1 and 15 mines"
+        );
+    }
+}
@@ -0,0 +1,464 @@
+use std::collections::HashSet;
+
+use crate::{
+    replay::PLAYBACK_SPEEDS,
+    theme::Theme,
+    types::{Difficulty, FirstClickPolicy},
+};
+
+/// Something a key can be bound to in the config
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum KeyAction {
+    Quit,
+    Difficulty,
+    NewGame,
+    Seed,
+    NoGuess,
+    FirstClick,
+    Hint,
+    Heatmap,
+    Undo,
+    Redo,
+    Replay,
+    ImportBoard,
+    ExportBoard,
+    WatchRawvf,
+    ExportRawvf,
+    Sort,
+    Stats,
+    Theme,
+}
+
+impl KeyAction {
+    const ALL: [Self; 18] = [
+        Self::Quit,
+        Self::Difficulty,
+        Self::NewGame,
+        Self::Seed,
+        Self::NoGuess,
+        Self::FirstClick,
+        Self::Hint,
+        Self::Heatmap,
+        Self::Undo,
+        Self::Redo,
+        Self::Replay,
+        Self::ImportBoard,
+        Self::ExportBoard,
+        Self::WatchRawvf,
+        Self::ExportRawvf,
+        Self::Sort,
+        Self::Stats,
+        Self::Theme,
+    ];
+
+    /// Name of the action in the `[keys]` section of the config
+    const fn as_static_str(self) -> &'static str {
+        match self {
+            Self::Quit => "quit",
+            Self::Difficulty => "difficulty",
+            Self::NewGame => "new_game",
+            Self::Seed => "seed",
+            Self::NoGuess => "no_guess",
+            Self::FirstClick => "first_click",
+            Self::Hint => "hint",
+            Self::Heatmap => "heatmap",
+            Self::Undo => "undo",
+            Self::Redo => "redo",
+            Self::Replay => "replay",
+            Self::ImportBoard => "import_board",
+            Self::ExportBoard => "export_board",
+            Self::WatchRawvf => "watch_rawvf",
+            Self::ExportRawvf => "export_rawvf",
+            Self::Sort => "sort",
+            Self::Stats => "stats",
+            Self::Theme => "theme",
+        }
+    }
+
+    const fn description(self) -> &'static str {
+        match self {
+            Self::Quit => "Quit, or close the difficulty menu",
+            Self::Difficulty => "Open the difficulty menu",
+            Self::NewGame => "Start a new game",
+            Self::Seed => "Play a seed",
+            Self::NoGuess => "Toggle boards that never need a guess",
+            Self::FirstClick => "Change the first click protection",
+            Self::Hint => "Show a hint",
+            Self::Heatmap => "Show mine probabilities",
+            Self::Undo => "Undo a move",
+            Self::Redo => "Redo a move",
+            Self::Replay => "Watch the last game again",
+            Self::ImportBoard => "Import a mine layout",
+            Self::ExportBoard => "Export the mine layout",
+            Self::WatchRawvf => "Watch a RAW video",
+            Self::ExportRawvf => "Export the last game as a RAW video",
+            Self::Sort => "Change what the leaderboard is sorted by",
+            Self::Stats => "Show your stats",
+            Self::Theme => "Change the colour theme",
+        }
+    }
+
+    const fn default_key(self) -> char {
+        match self {
+            Self::Quit => 'q',
+            Self::Difficulty => 'c',
+            Self::NewGame => 'r',
+            Self::Seed => 's',
+            Self::NoGuess => 'g',
+            Self::FirstClick => 'f',
+            Self::Hint => 'h',
+            Self::Heatmap => 'p',
+            Self::Undo => 'u',
+            Self::Redo => 'y',
+            Self::Replay => 'v',
+            Self::ImportBoard => 'i',
+            Self::ExportBoard => 'e',
+            Self::WatchRawvf => 'w',
+            Self::ExportRawvf => 'x',
+            Self::Sort => 'o',
+            Self::Stats => 't',
+            Self::Theme => 'm',
+        }
+    }
+
+    fn parse(str: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|action| action.as_static_str() == str)
+    }
+}
+
+/// Which key does what, letters match in either case
+#[derive(Clone)]
+pub struct KeyBindings {
+    keys: Vec<(KeyAction, char)>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            keys: KeyAction::ALL
+                .into_iter()
+                .map(|action| (action, action.default_key()))
+                .collect(),
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn action(&self, key: char) -> Option<KeyAction> {
+        self.keys
+            .iter()
+            .find(|(_, bound)| bound.eq_ignore_ascii_case(&key))
+            .map(|&(action, _)| action)
+    }
+
+    /// The key bound to an action, as shown in the instructions
+    pub fn key(&self, action: KeyAction) -> char {
+        self.keys
+            .iter()
+            .find(|(bound, _)| *bound == action)
+            .map_or_else(|| action.default_key(), |&(_, key)| key)
+            .to_ascii_uppercase()
+    }
+
+    /// Warnings for keys bound to more than one action, the first of the actions gets the key
+    fn clashes(&self) -> Vec<String> {
+        self.keys
+            .iter()
+            .enumerate()
+            .filter_map(|(index, &(action, key))| {
+                let (first, _) = self.keys[..index]
+                    .iter()
+                    .find(|(_, bound)| bound.eq_ignore_ascii_case(&key))?;
+                Some(format!(
+                    "'{key}' is bound to both '{}' and '{}'",
+                    first.as_static_str(),
+                    action.as_static_str()
+                ))
+            })
+            .collect()
+    }
+
+    fn bind(&mut self, action: KeyAction, key: char) {
+        if let Some(binding) = self.keys.iter_mut().find(|(bound, _)| *bound == action) {
+            binding.1 = key;
+        }
+    }
+}
+
+/// Settings read from the config file at startup
+#[derive(Clone)]
+pub struct Config {
+    pub difficulty: Difficulty,
+    /// Milliseconds between ticks, which is how often the screen is redrawn
+    pub tick_rate: u64,
+    pub keys: KeyBindings,
+    /// Whether middle clicking marks tiles with a question mark
+    pub question_marks: bool,
+    pub first_click: FirstClickPolicy,
+    /// Whether new games only deal boards that can be cleared without guessing
+    pub no_guess: bool,
+    pub theme: Theme,
+    /// Whether tiles and borders are drawn with plain ASCII instead of block characters
+    pub ascii: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            difficulty: Difficulty::Medium,
+            tick_rate: 30,
+            keys: KeyBindings::default(),
+            question_marks: true,
+            first_click: FirstClickPolicy::Opening,
+            no_guess: false,
+            theme: Theme::default(),
+            ascii: false,
+        }
+    }
+}
+
+impl Config {
+    /// Reads a config file, a small subset of TOML with `key = value` lines and a `[keys]` section.
+    /// Anything that can't be used is left at its default and reported in the warnings
+    pub fn parse(config: &str) -> (Self, Vec<String>) {
+        let mut parsed = Self::default();
+        let mut warnings = Vec::new();
+        let mut section = String::new();
+        let mut seen = HashSet::new();
+        for (number, line) in config.lines().enumerate() {
+            let number = number + 1;
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(name) = line
+                .strip_prefix('[')
+                .and_then(|line| line.strip_suffix(']'))
+            {
+                section = name.trim().to_string();
+                if section != "keys" {
+                    warnings.push(format!("Line {number}: unknown section '{section}'"));
+                }
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                warnings.push(format!("Line {number}: expected 'key = value'"));
+                continue;
+            };
+            let (key, value) = (key.trim(), unquote(value.trim()));
+            if !seen.insert((section.clone(), key.to_string())) {
+                warnings.push(format!(
+                    "Line {number}: '{key}' is set more than once, the last one is used"
+                ));
+            }
+            let warning = match section.as_str() {
+                "" => parsed.set(key, value),
+                "keys" => parsed.set_key(key, value),
+                _ => None,
+            };
+            if let Some(warning) = warning {
+                warnings.push(format!("Line {number}: {warning}"));
+            }
+        }
+        warnings.extend(parsed.keys.clashes());
+        (parsed, warnings)
+    }
+
+    /// Applies a setting from the top of the file, returns a warning if it can't be used
+    fn set(&mut self, key: &str, value: &str) -> Option<String> {
+        let invalid = || Some(format!("invalid value '{value}' for '{key}'"));
+        match key {
+            "difficulty" => match Difficulty::parse(value) {
+                Some(difficulty) => self.difficulty = difficulty,
+                None => return invalid(),
+            },
+            "tick_rate" => match value.parse() {
+                Ok(tick_rate) if tick_rate > 0 => self.tick_rate = tick_rate,
+                _ => return invalid(),
+            },
+            "theme" => match Theme::parse(value) {
+                Some(theme) => self.theme = theme,
+                None => return invalid(),
+            },
+            "ascii" => match value.parse() {
+                Ok(ascii) => self.ascii = ascii,
+                Err(_) => return invalid(),
+            },
+            "question_marks" => match value.parse() {
+                Ok(question_marks) => self.question_marks = question_marks,
+                Err(_) => return invalid(),
+            },
+            "no_guess" => match value.parse() {
+                Ok(no_guess) => self.no_guess = no_guess,
+                Err(_) => return invalid(),
+            },
+            "first_click" => match FirstClickPolicy::parse(value) {
+                Some(first_click) => self.first_click = first_click,
+                None => return invalid(),
+            },
+            _ => return Some(format!("unknown key '{key}'")),
+        }
+        None
+    }
+
+    /// Binds a key from the `[keys]` section, returns a warning if it can't be used
+    fn set_key(&mut self, action: &str, key: &str) -> Option<String> {
+        let Some(action) = KeyAction::parse(action) else {
+            return Some(format!("unknown action '{action}'"));
+        };
+        let mut chars = key.chars();
+        let (Some(key), None) = (chars.next(), chars.next()) else {
+            return Some(format!("'{key}' isn't a single key"));
+        };
+        self.keys.bind(action, key);
+        None
+    }
+
+    /// The config file with every setting at its default, each explained in a comment
+    pub fn default_file() -> String {
+        let config = Self::default();
+        let mut lines = vec![
+            String::from("# termsweeper config"),
+            String::new(),
+            String::from(
+                "# Difficulty new games start on: Easy, Medium, Hard or Custom WxH/M, like",
+            ),
+            String::from("# \"Custom 16x16/40\""),
+            format!("difficulty = \"{}\"", config.difficulty.as_string()),
+            String::new(),
+            String::from("# Milliseconds between redraws of the screen"),
+            format!("tick_rate = {}", config.tick_rate),
+            String::new(),
+            format!(
+                "# Colour theme: {}",
+                Theme::ALL.map(|theme| theme.name).join(", ")
+            ),
+            String::from(
+                "# colour-blind keeps apart the colours mixed up by red-green colour blindness",
+            ),
+            format!("theme = \"{}\"", config.theme.name),
+            String::new(),
+            String::from(
+                "# Whether to draw the board with plain ASCII, for terminals and fonts that",
+            ),
+            String::from("# garble block characters"),
+            format!("ascii = {}", config.ascii),
+            String::new(),
+            String::from("# Whether middle clicking marks a tile with a question mark"),
+            format!("question_marks = {}", config.question_marks),
+            String::new(),
+            String::from("# How much protection the first click gets: Unprotected, Safe tile or"),
+            String::from("# Opening"),
+            format!("first_click = \"{}\"", config.first_click.as_static_str()),
+            String::new(),
+            String::from("# Whether new games only deal boards that never need a guess"),
+            format!("no_guess = {}", config.no_guess),
+            String::new(),
+            String::from("# One key per action, letters work in either case"),
+            String::from("[keys]"),
+        ];
+        for action in KeyAction::ALL {
+            lines.push(format!("# {}", action.description()));
+            lines.push(format!(
+                "{} = \"{}\"",
+                action.as_static_str(),
+                action.default_key()
+            ));
+        }
+        let speeds: Vec<String> = PLAYBACK_SPEEDS.iter().map(u32::to_string).collect();
+        lines.extend([
+            String::new(),
+            String::from("# While a replay is playing these keys can't be changed: Space pauses,"),
+            format!(
+                "# Right or . steps, {} set the speed and Esc stops it",
+                speeds.join(", ")
+            ),
+        ]);
+        lines.push(String::new());
+        lines.join("\n")
+    }
+}
+
+/// Drops a `#` comment from the end of a line, unless the `#` is inside quotes
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (index, char) in line.char_indices() {
+        match char {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..index],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_file_is_read_back_without_warnings() {
+        let (config, warnings) = Config::parse(&Config::default_file());
+        assert!(warnings.is_empty(), "{warnings:?}");
+        assert!(config.difficulty == Config::default().difficulty);
+        assert!(Config::default_file().contains("Space pauses"));
+    }
+
+    #[test]
+    fn unknown_keys_are_reported() {
+        let (_, warnings) = Config::parse("colour = \"red\"\n[keys]\nfly = \"z\"\n[mouse]\n");
+        assert_eq!(
+            warnings,
+            [
+                "Line 1: unknown key 'colour'",
+                "Line 3: unknown action 'fly'",
+                "Line 4: unknown section 'mouse'",
+            ]
+        );
+    }
+
+    #[test]
+    fn bad_values_are_reported_and_left_at_their_default() {
+        let (config, warnings) = Config::parse(
+            "tick_rate = 0\ndifficulty = \"Impossible\"\nascii = yes\nno equals\n[keys]\nhint = \"hh\"",
+        );
+        assert_eq!(
+            warnings,
+            [
+                "Line 1: invalid value '0' for 'tick_rate'",
+                "Line 2: invalid value 'Impossible' for 'difficulty'",
+                "Line 3: invalid value 'yes' for 'ascii'",
+                "Line 4: expected 'key = value'",
+                "Line 6: 'hh' isn't a single key",
+            ]
+        );
+        let default = Config::default();
+        assert_eq!(config.tick_rate, default.tick_rate);
+        assert!(!config.ascii);
+        assert_eq!(config.keys.key(KeyAction::Hint), 'H');
+    }
+
+    #[test]
+    fn duplicates_are_reported() {
+        let (config, warnings) =
+            Config::parse("tick_rate = 10\ntick_rate = 20\n[keys]\nhint = \"z\"\nundo = \"Z\"\n");
+        assert_eq!(
+            warnings,
+            [
+                "Line 2: 'tick_rate' is set more than once, the last one is used",
+                "'Z' is bound to both 'hint' and 'undo'",
+            ]
+        );
+        assert_eq!(config.tick_rate, 20);
+        assert!(config.keys.action('z') == Some(KeyAction::Hint));
+    }
+}
@@ -0,0 +1,279 @@
+use std::ops::{Index, IndexMut};
+
+/// Offsets to the eight tiles surrounding a tile
+const NEIGHBOUR_OFFSETS: [(isize, isize); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+/// A rectangular grid stored row by row in a single contiguous buffer
+#[derive(Clone, PartialEq, Eq)]
+pub struct Grid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+}
+
+impl<T: Clone> Grid<T> {
+    pub fn new(width: usize, height: usize, value: T) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![value; width * height],
+        }
+    }
+}
+
+impl<T> Grid<T> {
+    pub const fn width(&self) -> usize {
+        self.width
+    }
+
+    pub const fn height(&self) -> usize {
+        self.height
+    }
+
+    pub const fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    pub const fn len(&self) -> usize {
+        self.width * self.height
+    }
+
+    pub const fn contains(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height
+    }
+
+    /// Position of a coordinate in the underlying buffer, the coordinate must be inside the grid
+    pub const fn index(&self, x: usize, y: usize) -> usize {
+        y * self.width + x
+    }
+
+    pub const fn coords(&self, index: usize) -> (usize, usize) {
+        (index % self.width, index / self.width)
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<&T> {
+        if self.contains(x, y) {
+            Some(&self.cells[self.index(x, y)])
+        } else {
+            None
+        }
+    }
+
+    pub fn get_mut(&mut self, x: usize, y: usize) -> Option<&mut T> {
+        if self.contains(x, y) {
+            let index = self.index(x, y);
+            Some(&mut self.cells[index])
+        } else {
+            None
+        }
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.cells.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, T> {
+        self.cells.iter_mut()
+    }
+
+    /// Every coordinate in the grid, in the same order as `iter`
+    pub fn positions(&self) -> impl Iterator<Item = (usize, usize)> {
+        let width = self.width;
+        (0..self.len()).map(move |index| (index % width, index / width))
+    }
+
+    /// Coordinates of the tiles surrounding a tile. The iterator doesn't borrow the grid, so the
+    /// grid can be changed while going through the neighbours
+    pub const fn neighbours(&self, x: usize, y: usize) -> Neighbours {
+        Neighbours {
+            x,
+            y,
+            width: self.width,
+            height: self.height,
+            offset: 0,
+        }
+    }
+
+    /// Coordinates of the surrounding tiles the closure returns true for, the closure may change
+    /// the tiles it is given
+    pub fn neighbours_where(
+        &mut self,
+        x: usize,
+        y: usize,
+        mut func: impl FnMut(&mut T) -> bool,
+    ) -> Vec<(usize, usize)> {
+        self.neighbours(x, y)
+            .filter(|&pos| func(&mut self[pos]))
+            .collect()
+    }
+
+    /// Number of surrounding tiles the closure returns true for
+    pub fn count_neighbours(&self, x: usize, y: usize, func: impl Fn(&T) -> bool) -> usize {
+        self.neighbours(x, y)
+            .filter(|&pos| func(&self[pos]))
+            .count()
+    }
+}
+
+impl<T> Index<(usize, usize)> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, (x, y): (usize, usize)) -> &T {
+        assert!(self.contains(x, y), "({x}, {y}) is outside of the grid");
+        &self.cells[self.index(x, y)]
+    }
+}
+
+impl<T> IndexMut<(usize, usize)> for Grid<T> {
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut T {
+        assert!(self.contains(x, y), "({x}, {y}) is outside of the grid");
+        let index = self.index(x, y);
+        &mut self.cells[index]
+    }
+}
+
+/// Iterator over the coordinates surrounding a tile, see [`Grid::neighbours`]
+#[derive(Clone)]
+pub struct Neighbours {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+    offset: usize,
+}
+
+impl Iterator for Neighbours {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(&(dx, dy)) = NEIGHBOUR_OFFSETS.get(self.offset) {
+            self.offset += 1;
+            let x = self.x.checked_add_signed(dx).filter(|&x| x < self.width);
+            let y = self.y.checked_add_signed(dy).filter(|&y| y < self.height);
+            if let (Some(x), Some(y)) = (x, y) {
+                return Some((x, y));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(neighbours: Neighbours) -> Vec<(usize, usize)> {
+        let mut neighbours: Vec<_> = neighbours.collect();
+        neighbours.sort_unstable();
+        neighbours
+    }
+
+    #[test]
+    fn index_and_coords_round_trip() {
+        let grid = Grid::new(4, 3, 0);
+        for (index, (x, y)) in grid.positions().enumerate() {
+            assert_eq!(grid.index(x, y), index);
+            assert_eq!(grid.coords(index), (x, y));
+        }
+        assert_eq!(grid.positions().last(), Some((3, 2)));
+        assert_eq!(grid.len(), 12);
+    }
+
+    #[test]
+    fn outside_the_grid_is_none() {
+        let mut grid = Grid::new(4, 3, 0);
+        assert!(grid.get(4, 0).is_none());
+        assert!(grid.get(0, 3).is_none());
+        assert!(grid.get_mut(usize::MAX, 0).is_none());
+        assert!(grid.get(3, 2).is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "outside of the grid")]
+    fn indexing_outside_the_grid_panics() {
+        // Without the check (4, 0) would land on (0, 1)
+        let grid = Grid::new(4, 3, 0);
+        let _ = grid[(4, 0)];
+    }
+
+    #[test]
+    fn neighbours_in_corners() {
+        let grid = Grid::new(4, 3, 0);
+        assert_eq!(sorted(grid.neighbours(0, 0)), [(0, 1), (1, 0), (1, 1)]);
+        assert_eq!(sorted(grid.neighbours(3, 0)), [(2, 0), (2, 1), (3, 1)]);
+        assert_eq!(sorted(grid.neighbours(0, 2)), [(0, 1), (1, 1), (1, 2)]);
+        assert_eq!(sorted(grid.neighbours(3, 2)), [(2, 1), (2, 2), (3, 1)]);
+    }
+
+    #[test]
+    fn neighbours_on_edges() {
+        let grid = Grid::new(4, 3, 0);
+        assert_eq!(
+            sorted(grid.neighbours(1, 0)),
+            [(0, 0), (0, 1), (1, 1), (2, 0), (2, 1)]
+        );
+        assert_eq!(
+            sorted(grid.neighbours(3, 1)),
+            [(2, 0), (2, 1), (2, 2), (3, 0), (3, 2)]
+        );
+        assert_eq!(
+            sorted(grid.neighbours(2, 2)),
+            [(1, 1), (1, 2), (2, 1), (3, 1), (3, 2)]
+        );
+        assert_eq!(
+            sorted(grid.neighbours(0, 1)),
+            [(0, 0), (0, 2), (1, 0), (1, 1), (1, 2)]
+        );
+    }
+
+    #[test]
+    fn neighbours_inside() {
+        let grid = Grid::new(4, 3, 0);
+        assert_eq!(
+            sorted(grid.neighbours(1, 1)),
+            [
+                (0, 0),
+                (0, 1),
+                (0, 2),
+                (1, 0),
+                (1, 2),
+                (2, 0),
+                (2, 1),
+                (2, 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn neighbours_of_thin_grids() {
+        assert_eq!(Grid::new(1, 1, 0).neighbours(0, 0).count(), 0);
+        assert_eq!(
+            sorted(Grid::new(3, 1, 0).neighbours(1, 0)),
+            [(0, 0), (2, 0)]
+        );
+        assert_eq!(sorted(Grid::new(1, 3, 0).neighbours(0, 2)), [(0, 1)]);
+    }
+
+    #[test]
+    fn counting_and_changing_neighbours() {
+        let mut grid = Grid::new(3, 3, 0);
+        let changed = grid.neighbours_where(0, 0, |value| {
+            *value += 1;
+            true
+        });
+        assert_eq!(changed.len(), 3);
+        assert_eq!(grid.count_neighbours(0, 0, |&value| value == 1), 3);
+        // (1, 1) was changed, but isn't its own neighbour
+        assert_eq!(grid.count_neighbours(1, 1, |&value| value == 1), 2);
+        assert_eq!(grid[(0, 0)], 0);
+    }
+}
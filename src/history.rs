@@ -0,0 +1,113 @@
+use crate::types::{GameState, TileState};
+
+/// A tile changing state, the tile is identified by its position in the grid buffer
+#[derive(Clone, Copy)]
+struct Change {
+    index: usize,
+    before: TileState,
+    after: TileState,
+}
+
+/// Everything one player action changed on the board
+struct Move {
+    changes: Vec<Change>,
+    state_before: GameState,
+    state_after: GameState,
+}
+
+/// Moves that can be undone and undone moves that can be redone
+#[derive(Default)]
+pub struct History {
+    done: Vec<Move>,
+    undone: Vec<Move>,
+    used: bool,
+}
+
+/// Tile changes to apply to the board to undo or redo a move
+pub struct Restore {
+    pub tiles: Vec<(usize, TileState)>,
+    pub state: GameState,
+}
+
+impl History {
+    /// Starts recording a move
+    pub fn begin(&mut self, state: GameState) {
+        self.done.push(Move {
+            changes: Vec::new(),
+            state_before: state,
+            state_after: state,
+        });
+    }
+
+    /// Finishes recording a move, moves that didn't change anything aren't kept. A move that is
+    /// kept drops any moves that were undone, since they can no longer be redone on top of it.
+    /// Returns whether the move was kept
+    pub fn end(&mut self, state: GameState) -> bool {
+        let Some(last) = self.done.last_mut() else {
+            return false;
+        };
+        last.state_after = state;
+        if last.changes.is_empty() && last.state_before == state {
+            self.done.pop();
+            false
+        } else {
+            self.undone.clear();
+            true
+        }
+    }
+
+    /// Adds a tile change to the last move, changes made after the move ended like the game over
+    /// animation revealing mines belong to it as well
+    pub fn record(&mut self, index: usize, before: TileState, after: TileState) {
+        if before == after {
+            return;
+        }
+        if let Some(last) = self.done.last_mut() {
+            last.changes.push(Change {
+                index,
+                before,
+                after,
+            });
+        }
+    }
+
+    pub fn undo(&mut self) -> Option<Restore> {
+        let last = self.done.pop()?;
+        self.used = true;
+        let restore = Restore {
+            tiles: last
+                .changes
+                .iter()
+                .rev()
+                .map(|change| (change.index, change.before))
+                .collect(),
+            state: last.state_before,
+        };
+        self.undone.push(last);
+        Some(restore)
+    }
+
+    pub fn redo(&mut self) -> Option<Restore> {
+        let next = self.undone.pop()?;
+        let restore = Restore {
+            tiles: next
+                .changes
+                .iter()
+                .map(|change| (change.index, change.after))
+                .collect(),
+            state: next.state_after,
+        };
+        self.done.push(next);
+        Some(restore)
+    }
+
+    /// Records that a move was undone, for games restored from a save
+    pub const fn mark_used(&mut self) {
+        self.used = true;
+    }
+
+    /// Whether anything was ever undone, such games are practice and aren't scored
+    pub const fn used(&self) -> bool {
+        self.used
+    }
+}
@@ -1,98 +1,470 @@
 use std::{
+    env,
     fs::{self, File, OpenOptions},
-    io::{BufRead, BufReader, Write},
+    io::{ErrorKind, Write},
     path::{Path, PathBuf},
-    time::Duration,
+    process,
+    sync::OnceLock,
 };
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 
-use crate::types::{Difficulty, Score};
+use crate::leaderboard::{leaderboard_to_string, parse_leaderboard, Score};
 
-fn get_leaderboard_path() -> Option<PathBuf> {
-    let mut path = home::home_dir()?;
+/// Scores from games played without any help
+const LEADERBOARD_FILE: &str = "leaderboard.txt";
+/// Scores from games where hints were used, kept apart so they don't compete with unassisted runs
+const HINTED_LEADERBOARD_FILE: &str = "leaderboard_hinted.txt";
+/// The game that was being played when the program was last closed
+const SAVE_FILE: &str = "save.txt";
+/// Wins, losses and streaks for every difficulty
+const STATS_FILE: &str = "stats.txt";
+/// Game shared with other Minesweeper programs in the RAW video format
+const RAWVF_FILE: &str = "game.rawvf";
+/// Mine layout shared between players, written on export and read on import
+const BOARD_FILE: &str = "board.txt";
+/// Every move of the last finished game, so it can be watched again
+const REPLAY_FILE: &str = "replay.txt";
+/// Settings read at startup
+const CONFIG_FILE: &str = "config.toml";
+/// Locked while the leaderboard is read, merged and written back
+const LOCK_FILE: &str = "leaderboard.lock";
+
+/// Environment variable naming a folder to keep every file in, instead of the usual folders
+const DIR_VAR: &str = "TERMSWEEPER_DIR";
+
+/// The kinds of files, each kept in its own folder following the XDG base directory spec
+#[derive(Clone, Copy)]
+enum Dir {
+    /// The config file
+    Config,
+    /// Scores, stats and exported games
+    Data,
+    /// The saved game and the replay of the last game
+    State,
+}
+
+/// Every file that used to be kept in the config folder, along with where it belongs now
+const OLD_FILES: [(&str, Dir); 8] = [
+    (LEADERBOARD_FILE, Dir::Data),
+    (HINTED_LEADERBOARD_FILE, Dir::Data),
+    (STATS_FILE, Dir::Data),
+    (RAWVF_FILE, Dir::Data),
+    (BOARD_FILE, Dir::Data),
+    (LOCK_FILE, Dir::Data),
+    (SAVE_FILE, Dir::State),
+    (REPLAY_FILE, Dir::State),
+];
+
+/// Folder given on the command line for scores, stats and saves
+static DATA_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Keeps data and state in `dir` instead of the usual folders, can only be set once
+pub fn set_data_dir(dir: PathBuf) {
+    let _ = DATA_DIR.set(dir);
+}
+
+fn dir_path(dir: Dir) -> Option<PathBuf> {
+    if let (Dir::Data | Dir::State, Some(data_dir)) = (dir, DATA_DIR.get()) {
+        return Some(data_dir.clone());
+    }
+    if let Some(root) = env::var_os(DIR_VAR).filter(|root| !root.is_empty()) {
+        return Some(PathBuf::from(root));
+    }
+    let home = home::home_dir()?;
     if cfg!(windows) {
-        path.push(Path::new("AppData"));
-        path.push(Path::new("Local"));
-    } else {
-        path.push(Path::new(".config"));
-    }
-    path.push(Path::new("termsweeper"));
-    Some(path)
-}
-
-fn parse_line(str: &str) -> Option<Score> {
-    if let Some((difficulty, time)) = str.split_once(": ") {
-        let difficulty = match difficulty {
-            "Easy" => Some(Difficulty::Easy),
-            "Medium" => Some(Difficulty::Medium),
-            "Hard" => Some(Difficulty::Hard),
-            _ => None,
+        return Some(home.join("AppData").join("Local").join("termsweeper"));
+    }
+    let (var, default): (&str, &[&str]) = match dir {
+        Dir::Config => ("XDG_CONFIG_HOME", &[".config"]),
+        Dir::Data => ("XDG_DATA_HOME", &[".local", "share"]),
+        Dir::State => ("XDG_STATE_HOME", &[".local", "state"]),
+    };
+    // The spec says relative paths are invalid and should be ignored
+    let base = env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|base| base.is_absolute())
+        .unwrap_or_else(|| default.iter().fold(home, |path, part| path.join(part)));
+    Some(base.join("termsweeper"))
+}
+
+fn file_path(dir: Dir, file_name: &str) -> Result<PathBuf> {
+    let path = dir_path(dir).ok_or_else(|| anyhow!("No home folder"))?;
+    Ok(path.join(file_name))
+}
+
+/// `~/.config/termsweeper`, where everything used to be kept, unless the files are kept somewhere
+/// chosen by the player
+fn old_dir() -> Option<PathBuf> {
+    if cfg!(windows)
+        || DATA_DIR.get().is_some()
+        || env::var_os(DIR_VAR).is_some_and(|root| !root.is_empty())
+    {
+        return None;
+    }
+    home::home_dir().map(|home| home.join(".config").join("termsweeper"))
+}
+
+/// The old place of a file that hasn't been moved to where it belongs yet
+fn unmoved_path(dir: Dir, file_name: &str) -> Result<Option<PathBuf>> {
+    let Some(old_dir) = old_dir() else {
+        return Ok(None);
+    };
+    let (old, new) = (old_dir.join(file_name), file_path(dir, file_name)?);
+    Ok((old != new && old.exists() && !new.exists()).then_some(old))
+}
+
+/// Where a file is read from, its old place if it hasn't been moved yet so reading never has to
+/// move anything
+fn readable_path(dir: Dir, file_name: &str) -> Result<PathBuf> {
+    unmoved_path(dir, file_name)?.map_or_else(|| file_path(dir, file_name), Ok)
+}
+
+/// Moves files out of `~/.config/termsweeper`, where everything used to be kept, into the folders
+/// they belong in. Files already in their new place are left alone, so this only ever happens once
+pub fn migrate_old_files() -> Result<()> {
+    for (file_name, dir) in OLD_FILES {
+        let Some(old) = unmoved_path(dir, file_name)? else {
+            continue;
         };
-        let time = time.parse().ok();
-        if let (Some(difficulty), Some(time)) = (difficulty, time) {
-            let time = Duration::from_secs(time);
-            Some(Score::new(difficulty, time))
-        } else {
-            None
+        let new = file_path(dir, file_name)?;
+        if let Some(parent) = new.parent() {
+            fs::create_dir_all(parent)?;
         }
-    } else {
-        None
-    }
-}
-
-pub fn read_leaderboard() -> Option<Vec<Score>> {
-    let mut path = get_leaderboard_path()?;
-    path.push(Path::new("leaderboard.txt"));
-    let path = Path::new(&path);
-    File::open(path).map_or(None, |input| {
-        let buffered = BufReader::new(input);
-        let mut scores = Vec::new();
-        for line in buffered.lines() {
-            let score = line.ok().and_then(|line| parse_line(&line));
-            if let Some(score) = score {
-                scores.push(score);
-            }
+        // Renaming fails across file systems, fall back to copying
+        if fs::rename(&old, &new).is_err() {
+            fs::copy(&old, &new)?;
+            fs::remove_file(&old)?;
         }
-        if scores.is_empty() {
-            None
-        } else {
-            Some(scores)
+    }
+    Ok(())
+}
+
+/// Reads one of the leaderboard files, along with whether it is in the old format
+fn read_scores(path: &Path) -> Result<(Vec<Score>, bool)> {
+    read_path(path)?.map_or_else(
+        || Ok((Vec::new(), false)),
+        |contents| parse_leaderboard(&contents),
+    )
+}
+
+/// Reads both leaderboards, along with whether either of them is in the old format
+fn read_all_scores(dir: &Path) -> Result<(Vec<Score>, bool)> {
+    let (mut scores, legacy) = read_scores(&dir.join(LEADERBOARD_FILE))?;
+    let (mut hinted, hinted_legacy) = read_scores(&dir.join(HINTED_LEADERBOARD_FILE))?;
+    scores.append(&mut hinted);
+    Ok((scores, legacy || hinted_legacy))
+}
+
+/// Reads every score without changing any files, a leaderboard that can't be read is an error
+pub fn read_leaderboard() -> Result<Vec<Score>> {
+    let (mut scores, _) = read_scores(&readable_path(Dir::Data, LEADERBOARD_FILE)?)?;
+    let (mut hinted, _) = read_scores(&readable_path(Dir::Data, HINTED_LEADERBOARD_FILE)?)?;
+    scores.append(&mut hinted);
+    Ok(scores)
+}
+
+/// Rewrites leaderboards in the old format in the current one
+pub fn migrate_leaderboard() -> Result<()> {
+    let dir = dir_path(Dir::Data).ok_or_else(|| anyhow!("No home folder"))?;
+    let (_, legacy) = read_all_scores(&dir)?;
+    if legacy {
+        update_leaderboard(&dir, &[])?;
+    }
+    Ok(())
+}
+
+/// Writes the scores out, hinted scores go to their own file
+fn store_scores(dir: &Path, scores: Vec<Score>) -> Result<()> {
+    let (hinted, unassisted): (Vec<Score>, Vec<Score>) =
+        scores.into_iter().partition(|score| score.hints > 0);
+
+    for (file_name, mut scores) in [
+        (LEADERBOARD_FILE, unassisted),
+        (HINTED_LEADERBOARD_FILE, hinted),
+    ] {
+        // Remove duplicates
+        scores.sort_by_cached_key(Score::as_string);
+        scores.dedup();
+
+        if !scores.is_empty() {
+            write_path(&dir.join(file_name), &leaderboard_to_string(&scores))?;
         }
-    })
+    }
+
+    Ok(())
+}
+
+/// Merges scores into the leaderboards in `dir`. The lock is held from reading to writing so
+/// scores written by another instance in the meantime aren't lost
+fn update_leaderboard(dir: &Path, leaderboard: &[Score]) -> Result<()> {
+    let _lock = lock(dir)?;
+    // A leaderboard that can't be read isn't overwritten
+    let (mut scores, _) = read_all_scores(dir)?;
+    scores.extend_from_slice(leaderboard);
+    store_scores(dir, scores)
 }
 
 pub fn write_leaderboard(leaderboard: &[Score]) -> Result<()> {
-    // Combine new and old leaderboards, convert to strings
-    let mut existing = read_leaderboard().unwrap_or_else(|| Vec::with_capacity(leaderboard.len()));
-    leaderboard
-        .iter()
-        .copied()
-        .for_each(|score| existing.push(score));
-    let mut existing: Vec<String> = existing
+    let dir = dir_path(Dir::Data).ok_or_else(|| anyhow!("No home folder"))?;
+    update_leaderboard(&dir, leaderboard)
+}
+
+/// Takes an advisory lock on the folder, held until the file is dropped
+fn lock(dir: &Path) -> Result<File> {
+    fs::create_dir_all(dir)?;
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(dir.join(LOCK_FILE))?;
+    file.lock()?;
+    Ok(file)
+}
+
+/// Name recorded with new scores, the name of the user playing
+pub fn player_name() -> Option<String> {
+    ["USER", "USERNAME"]
         .into_iter()
-        .map(|score| score.as_string())
-        .collect();
-
-    // Remove duplicates
-    existing.sort_unstable();
-    existing.dedup();
-
-    // Ensure the folder and file exist
-    let mut path = get_leaderboard_path().ok_or_else(|| anyhow!("No config folder"))?;
-    fs::create_dir_all(Path::new(&path))?;
-    path.push(Path::new("leaderboard.txt"));
-    let file = Path::new(&path);
-
-    let mut file = if file.exists() {
-        OpenOptions::new().write(true).open(file)?
-    } else {
-        File::create(file)?
-    };
-    for mut score in existing {
-        score.push('\n');
-        file.write_all(score.as_bytes())?;
+        .find_map(|key| std::env::var(key).ok())
+        .and_then(|name| name.lines().next().map(|name| name.trim().to_string()))
+        .filter(|name| !name.is_empty())
+}
+
+/// Reads a file, returns `None` if it doesn't exist
+fn read_path(path: &Path) -> Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
     }
+}
 
-    Ok(())
+/// Writes a file by writing a temporary file next to it and renaming that over it, so the file
+/// is never left half written if the program is interrupted
+fn write_path(path: &Path, contents: &str) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(format!(".{}.tmp", process::id()));
+    let temp = PathBuf::from(temp);
+
+    let result = File::create(&temp)
+        .and_then(|mut file| {
+            file.write_all(contents.as_bytes())?;
+            file.sync_all()
+        })
+        .and_then(|()| fs::rename(&temp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    Ok(result?)
+}
+
+/// Reads a file from one of the folders, returns `None` if it doesn't exist
+fn read_file(dir: Dir, file_name: &str) -> Result<Option<String>> {
+    read_path(&readable_path(dir, file_name)?)
+}
+
+fn write_file(dir: Dir, file_name: &str, contents: &str) -> Result<()> {
+    write_path(&file_path(dir, file_name)?, contents)
+}
+
+/// Reads the config file, from `path` if one was given on the command line
+pub fn read_config(path: Option<&Path>) -> Result<Option<String>> {
+    match path {
+        Some(path) => Ok(Some(fs::read_to_string(path)?)),
+        None => read_file(Dir::Config, CONFIG_FILE),
+    }
+}
+
+/// Writes the default config for the player to edit, an existing config is never replaced
+pub fn write_default_config(config: &str, path: Option<&Path>) -> Result<PathBuf> {
+    let path = path.map_or_else(
+        || file_path(Dir::Config, CONFIG_FILE),
+        |path| Ok(path.to_path_buf()),
+    )?;
+    if path.exists() {
+        bail!("{} already exists", path.display());
+    }
+    write_path(&path, config)?;
+    Ok(path)
+}
+
+/// Reads the saved game, if there is one
+pub fn read_save() -> Result<Option<String>> {
+    read_file(Dir::State, SAVE_FILE)
+}
+
+pub fn write_save(save: &str) -> Result<()> {
+    write_file(Dir::State, SAVE_FILE, save)
+}
+
+pub fn remove_save() -> Result<()> {
+    match fs::remove_file(file_path(Dir::State, SAVE_FILE)?) {
+        Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Reads the replay of the last finished game, if there is one
+pub fn read_replay() -> Result<Option<String>> {
+    read_file(Dir::State, REPLAY_FILE)
+}
+
+pub fn write_replay(replay: &str) -> Result<()> {
+    write_file(Dir::State, REPLAY_FILE, replay)
+}
+
+/// Where boards are exported to and imported from
+pub fn board_path() -> Option<PathBuf> {
+    file_path(Dir::Data, BOARD_FILE).ok()
+}
+
+pub fn read_board() -> Result<Option<String>> {
+    read_file(Dir::Data, BOARD_FILE)
+}
+
+pub fn write_board(board: &str) -> Result<()> {
+    write_file(Dir::Data, BOARD_FILE, board)
+}
+
+/// Where RAW video files are exported to and imported from
+pub fn rawvf_path() -> Option<PathBuf> {
+    file_path(Dir::Data, RAWVF_FILE).ok()
+}
+
+pub fn read_rawvf() -> Result<Option<String>> {
+    read_file(Dir::Data, RAWVF_FILE)
+}
+
+pub fn write_rawvf(rawvf: &str) -> Result<()> {
+    write_file(Dir::Data, RAWVF_FILE, rawvf)
+}
+
+pub fn read_stats() -> Result<Option<String>> {
+    read_file(Dir::Data, STATS_FILE)
+}
+
+pub fn write_stats(stats: &str) -> Result<()> {
+    write_file(Dir::Data, STATS_FILE, stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use super::*;
+    use crate::types::Difficulty;
+
+    /// A fresh folder for a test to write to
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("termsweeper-{name}-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn score(millis: u64) -> Score {
+        Score {
+            difficulty: Difficulty::Easy,
+            time: Duration::from_millis(millis),
+            hints: 0,
+            metrics: None,
+            name: Some(String::from("test")),
+            date: None,
+            size: Some((9, 9)),
+            mines: 10,
+            seed: Some(millis),
+            no_guess: false,
+            first_click: None,
+        }
+    }
+
+    #[test]
+    fn shorter_rewrite_leaves_nothing_behind() {
+        let dir = test_dir("rewrite");
+        let path = dir.join("file.txt");
+        write_path(&path, "a much longer first version\n").unwrap();
+        write_path(&path, "short\n").unwrap();
+        assert_eq!(read_path(&path).unwrap().as_deref(), Some("short\n"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn interrupted_write_keeps_old_leaderboard() {
+        let dir = test_dir("interrupted");
+        update_leaderboard(&dir, &[score(1000)]).unwrap();
+
+        // A write that died before the rename leaves only its temporary file behind
+        let path = dir.join(LEADERBOARD_FILE);
+        fs::write(dir.join("leaderboard.txt.1.tmp"), "termsweeper leaderb").unwrap();
+        let (scores, _) = read_all_scores(&dir).unwrap();
+        assert!(scores == [score(1000)]);
+
+        update_leaderboard(&dir, &[score(2000)]).unwrap();
+        let (scores, _) = read_all_scores(&dir).unwrap();
+        assert_eq!(scores.len(), 2);
+
+        // A write that fails doesn't touch the file it was replacing or leave anything behind
+        let before = fs::read_dir(&dir).unwrap().count();
+        let blocked = dir.join("blocked");
+        fs::create_dir_all(blocked.join("inside")).unwrap();
+        assert!(write_path(&blocked, "contents").is_err());
+        assert!(blocked.join("inside").is_dir());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), before + 1);
+        assert!(read_path(&path).unwrap().is_some());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn concurrent_writers_keep_every_score() {
+        let dir = test_dir("concurrent");
+        let writers: Vec<_> = (0..8)
+            .map(|writer| {
+                let dir = dir.clone();
+                thread::spawn(move || {
+                    for game in 0..10 {
+                        update_leaderboard(&dir, &[score(writer * 100 + game)]).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        let (scores, legacy) = read_all_scores(&dir).unwrap();
+        assert!(!legacy);
+        assert_eq!(scores.len(), 80);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn old_leaderboard_is_migrated() {
+        let dir = test_dir("migrate");
+        fs::write(dir.join(LEADERBOARD_FILE), "Easy: 12\nEasy: 9\n").unwrap();
+        let (scores, legacy) = read_all_scores(&dir).unwrap();
+        assert!(legacy);
+        assert_eq!(scores.len(), 2);
+        // Reading alone leaves the old file as it was
+        let old = fs::read_to_string(dir.join(LEADERBOARD_FILE)).unwrap();
+        assert_eq!(old, "Easy: 12\nEasy: 9\n");
+
+        update_leaderboard(&dir, &[]).unwrap();
+        let (migrated, legacy) = read_all_scores(&dir).unwrap();
+        assert!(!legacy);
+        assert_eq!(migrated.len(), 2);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn corrupt_leaderboard_is_an_error() {
+        let dir = test_dir("corrupt");
+        fs::write(dir.join(LEADERBOARD_FILE), "not a score\n").unwrap();
+        assert!(read_all_scores(&dir).is_err());
+        fs::write(dir.join(LEADERBOARD_FILE), "\n").unwrap();
+        assert!(read_all_scores(&dir).unwrap().0.is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
 }
@@ -0,0 +1,99 @@
+use anyhow::{bail, Result};
+
+use crate::{grid::Grid, types::tile_count};
+
+const MINE: char = '*';
+const SAFE: char = '.';
+
+/// Writes a mine layout with one row per line
+pub fn layout_to_lines(mines: &Grid<bool>) -> Vec<String> {
+    (0..mines.height())
+        .map(|y| {
+            (0..mines.width())
+                .map(|x| if mines[(x, y)] { MINE } else { SAFE })
+                .collect()
+        })
+        .collect()
+}
+
+/// Reads a mine layout written by `layout_to_lines`, blank lines before and after it are skipped.
+/// Errors point at the offending line and column counting from `first_line`
+pub fn parse_layout<'a>(
+    lines: impl IntoIterator<Item = &'a str>,
+    first_line: usize,
+) -> Result<Grid<bool>> {
+    let mut rows: Vec<Vec<char>> = lines
+        .into_iter()
+        .map(|line| line.trim_end().chars().collect())
+        .collect();
+    while rows.last().is_some_and(Vec::is_empty) {
+        rows.pop();
+    }
+    let blank = rows.iter().take_while(|row| row.is_empty()).count();
+    rows.drain(..blank);
+    let first_line = first_line + blank;
+    let Some(width) = rows.first().map(Vec::len).filter(|&width| width > 0) else {
+        bail!("Line {first_line}: the layout is empty");
+    };
+
+    tile_count(width, rows.len())?;
+    let mut mines = Grid::new(width, rows.len(), false);
+    for (y, row) in rows.iter().enumerate() {
+        let line = first_line + y;
+        if row.len() != width {
+            bail!(
+                "Line {line}: expected {width} tiles like the first row but found {}",
+                row.len()
+            );
+        }
+        for (x, &char) in row.iter().enumerate() {
+            mines[(x, y)] = match char {
+                MINE => true,
+                SAFE => false,
+                _ => bail!(
+                    "Line {line}, column {}: expected '{MINE}' or '{SAFE}' but found '{char}'",
+                    x + 1
+                ),
+            };
+        }
+    }
+    Ok(mines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(layout: &str) -> String {
+        parse_layout(layout.lines(), 1).err().unwrap().to_string()
+    }
+
+    #[test]
+    fn layouts_round_trip() {
+        let mines = parse_layout("\n\n.*.\n*..\n\n".lines(), 1).unwrap();
+        assert_eq!(mines.size(), (3, 2));
+        assert_eq!(layout_to_lines(&mines), [".*.", "*.."]);
+    }
+
+    #[test]
+    fn ragged_rows_are_rejected() {
+        assert_eq!(
+            error("\n...\n..\n"),
+            "Line 3: expected 3 tiles like the first row but found 2"
+        );
+    }
+
+    #[test]
+    fn unknown_characters_are_rejected() {
+        assert_eq!(
+            error("...\n.x.\n"),
+            "Line 2, column 2: expected '*' or '.' but found 'x'"
+        );
+    }
+
+    #[test]
+    fn empty_layouts_are_rejected() {
+        assert_eq!(error(""), "Line 1: the layout is empty");
+        assert_eq!(error("\n  \n\n"), "Line 1: the layout is empty");
+    }
+}
@@ -0,0 +1,249 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Result};
+use ratatui::{
+    style::Stylize,
+    text::{Line, Span},
+    widgets::ListItem,
+};
+
+use crate::{
+    metrics::{Metrics, ScoreSort},
+    theme::Theme,
+    types::{format_seconds, parse_seconds, Board, Difficulty, FirstClickPolicy},
+};
+
+/// Version of the leaderboard file format, files written by other versions are left alone
+const LEADERBOARD_VERSION: u32 = 1;
+const LEADERBOARD_HEADER: &str = "termsweeper leaderboard";
+
+/// A won game. Scores carried over from the old one line format only have the difficulty, time,
+/// hints and metrics
+#[derive(Clone, PartialEq, Eq)]
+pub struct Score {
+    pub difficulty: Difficulty,
+    pub time: Duration,
+    pub hints: usize,
+    /// Scores from before metrics were recorded don't have any
+    pub metrics: Option<Metrics>,
+    pub name: Option<String>,
+    /// When the game was won, in seconds since the Unix epoch
+    pub date: Option<u64>,
+    /// Width and height of the board, the preset difficulties shrink to fit small terminals
+    pub size: Option<(usize, usize)>,
+    pub mines: usize,
+    pub seed: Option<u64>,
+    pub no_guess: bool,
+    pub first_click: Option<FirstClickPolicy>,
+}
+
+impl Score {
+    /// The score for a board that has just been won
+    pub fn from_board(board: &Board, name: Option<String>) -> Self {
+        Self {
+            difficulty: board.difficulty,
+            time: board.state().elapsed(),
+            hints: board.hints_used(),
+            metrics: Some(board.metrics()),
+            name,
+            date: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|since| since.as_secs()),
+            size: Some(board.get_board_size()),
+            mines: board.mine_count(),
+            seed: Some(board.seed()),
+            no_guess: board.no_guess,
+            first_click: Some(board.first_click),
+        }
+    }
+
+    /// One `key: value` line per field, fields the score doesn't have are left out
+    pub fn as_string(&self) -> String {
+        let mut fields = vec![("difficulty", self.difficulty.as_string())];
+        if let Some(name) = &self.name {
+            fields.push(("name", name.clone()));
+        }
+        if let Some(date) = self.date {
+            fields.push(("date", format_date(date)));
+        }
+        if let Some((width, height)) = self.size {
+            fields.push(("width", width.to_string()));
+            fields.push(("height", height.to_string()));
+        }
+        fields.push(("mines", self.mines.to_string()));
+        if let Some(seed) = self.seed {
+            fields.push(("seed", seed.to_string()));
+        }
+        fields.push(("time", format_seconds(self.time)));
+        fields.push(("hints", self.hints.to_string()));
+        fields.push(("no guess", self.no_guess.to_string()));
+        if let Some(first_click) = self.first_click {
+            fields.push(("first click", first_click.as_static_str().to_string()));
+        }
+        if let Some(metrics) = self.metrics {
+            fields.push(("3bv", metrics.bbbv.to_string()));
+            fields.push(("clicks", metrics.clicks.to_string()));
+            fields.push(("effective clicks", metrics.effective_clicks.to_string()));
+        }
+        fields
+            .into_iter()
+            .map(|(key, value)| format!("{key}: {value}"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Reads a score written by `as_string`, the difficulty and time are the only required fields
+    fn parse(entry: &str) -> Option<Self> {
+        let values: HashMap<&str, &str> = entry
+            .lines()
+            .filter_map(|line| line.split_once(": "))
+            .collect();
+        let count = |key: &str| values.get(key).and_then(|value| value.parse().ok());
+        let difficulty = Difficulty::parse(values.get("difficulty")?)?;
+        let metrics = match (count("3bv"), count("clicks"), count("effective clicks")) {
+            (Some(bbbv), Some(clicks), Some(effective_clicks)) => Some(Metrics {
+                bbbv,
+                clicks,
+                effective_clicks,
+            }),
+            _ => None,
+        };
+        Some(Self {
+            difficulty,
+            time: parse_seconds(values.get("time")?)?,
+            hints: count("hints").unwrap_or(0),
+            metrics,
+            name: values.get("name").map(ToString::to_string),
+            date: values.get("date").and_then(|date| parse_date(date)),
+            size: count("width").zip(count("height")),
+            mines: count("mines").unwrap_or_else(|| difficulty.mines()),
+            seed: values.get("seed").and_then(|seed| seed.parse().ok()),
+            no_guess: values.get("no guess") == Some(&"true"),
+            first_click: values
+                .get("first click")
+                .and_then(|policy| FirstClickPolicy::parse(policy)),
+        })
+    }
+
+    /// Reads a line of the old leaderboard format, `<difficulty>: <seconds>`
+    fn parse_legacy(line: &str) -> Option<Self> {
+        let (difficulty, time) = line.split_once(": ")?;
+        let difficulty = Difficulty::parse(difficulty)?;
+        Some(Self {
+            difficulty,
+            time: parse_seconds(time)?,
+            hints: 0,
+            metrics: None,
+            name: None,
+            date: None,
+            size: None,
+            mines: difficulty.mines(),
+            seed: None,
+            no_guess: false,
+            first_click: None,
+        })
+    }
+
+    /// Shows the time, along with whatever the leaderboard is sorted by if that isn't the time
+    pub fn as_list_item(&self, sort: ScoreSort, theme: &Theme) -> ListItem<'_> {
+        let difficulty = self.difficulty.as_span(theme);
+        let mid = Span::raw(": ");
+        let time = Span::raw(format_seconds(self.time)).fg(theme.time).bold();
+        let mut spans = vec![difficulty, mid, time, Span::raw("s")];
+        if let Some(name) = &self.name {
+            spans.push(Span::raw(format!(" {name}")).fg(theme.info));
+        }
+        if let Some(value) = sort.value(self).filter(|_| sort != ScoreSort::Time) {
+            spans.push(Span::raw(format!(" {}", sort.format(value))).fg(theme.good));
+        }
+        if self.hints > 0 {
+            spans.push(Span::raw(format!(" ({} hints)", self.hints)).fg(theme.muted));
+        }
+        let text = Line::default().spans(spans).centered();
+        ListItem::new(text)
+    }
+}
+
+/// Writes a leaderboard file, scores are separated by blank lines
+pub fn leaderboard_to_string(scores: &[Score]) -> String {
+    let mut entries = vec![format!("{LEADERBOARD_HEADER} {LEADERBOARD_VERSION}")];
+    entries.extend(scores.iter().map(Score::as_string));
+    entries.join("\n\n") + "\n"
+}
+
+/// Reads a leaderboard file, files in the old one line per score format are read too. Along with
+/// the scores comes whether the file is in the old format and should be rewritten. Scores that
+/// can't be read are skipped, but a file with nothing readable in it is an error
+pub fn parse_leaderboard(contents: &str) -> Result<(Vec<Score>, bool)> {
+    let Some(rest) = contents.strip_prefix(LEADERBOARD_HEADER) else {
+        let scores: Vec<Score> = contents.lines().filter_map(Score::parse_legacy).collect();
+        if scores.is_empty() && !contents.trim().is_empty() {
+            bail!("The leaderboard file isn't in a known format");
+        }
+        let legacy = !scores.is_empty();
+        return Ok((scores, legacy));
+    };
+    let (version, entries) = rest.split_once('\n').unwrap_or((rest, ""));
+    if version.trim() != LEADERBOARD_VERSION.to_string() {
+        bail!(
+            "Leaderboard file version {} isn't supported",
+            version.trim()
+        );
+    }
+    let scores = entries
+        .split("\n\n")
+        .filter(|entry| !entry.trim().is_empty())
+        .filter_map(Score::parse)
+        .collect();
+    Ok((scores, false))
+}
+
+/// Writes a Unix timestamp as a UTC date and time, like `2024-03-01T12:30:00Z`
+pub fn format_date(timestamp: u64) -> String {
+    let (days, secs) = (timestamp / 86_400, timestamp % 86_400);
+    // Days to a civil date, from Howard Hinnant's date algorithms with March as the first month
+    let days = days + 719_468;
+    let (era, day_of_era) = (days / 146_097, days % 146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// Reads a date written by `format_date` back into a Unix timestamp
+fn parse_date(date: &str) -> Option<u64> {
+    let (date, time) = date.strip_suffix('Z')?.split_once('T')?;
+    let parts = |str: &str, separator| -> Option<Vec<u64>> {
+        let parts: Vec<u64> = str
+            .split(separator)
+            .map(|part| part.parse().ok())
+            .collect::<Option<_>>()?;
+        (parts.len() == 3).then_some(parts)
+    };
+    let (date, time) = (parts(date, '-')?, parts(time, ':')?);
+    let (year, month, day) = (date[0], date[1], date[2]);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || time[0] > 23 || time[1] > 59 {
+        return None;
+    }
+    // The inverse of `format_date`, with January and February counted at the end of the year
+    let year = year.checked_sub(u64::from(month <= 2))?;
+    let (era, year_of_era) = (year / 400, year % 400);
+    let month = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = (era * 146_097 + day_of_era).checked_sub(719_468)?;
+    Some(days * 86_400 + time[0] * 3600 + time[1] * 60 + time[2])
+}
@@ -4,24 +4,161 @@ use crate::term::{
     tui::Tui,
     update::{handle_keys, handle_mouse},
 };
-use anyhow::{Ok, Result};
+use anyhow::{bail, Ok, Result};
+use cli::{Cli, Command, HELP};
+use config::Config;
 use ratatui::{prelude::CrosstermBackend, Terminal};
+use stats::Stats;
+use types::{check_compatible, Difficulty};
 
+mod cli;
+mod config;
+mod grid;
+mod history;
 mod io;
+mod layout;
+mod leaderboard;
+mod metrics;
+mod rawvf;
+mod replay;
+mod report;
+mod solver;
+mod stats;
 mod term;
+mod theme;
 mod types;
 
+/// Reads the config, warnings about it are printed before the terminal is taken over so they
+/// stay visible after exit. A config named on the command line has to exist
+fn load_config(cli: &Cli) -> Result<(Config, Vec<String>)> {
+    let (mut config, warnings) = match io::read_config(cli.config.as_deref()) {
+        Result::Ok(config) => config
+            .map(|config| Config::parse(&config))
+            .unwrap_or_default(),
+        Err(err) if cli.config.is_some() => return Err(err.context("Couldn't read the config")),
+        Err(err) => (
+            Config::default(),
+            vec![format!("Couldn't read the config: {err}")],
+        ),
+    };
+    for warning in &warnings {
+        eprintln!("Config: {warning}");
+    }
+
+    // The command line takes priority over the config
+    if let Some(difficulty) = cli.difficulty {
+        config.difficulty = difficulty;
+    }
+    config.no_guess |= cli.no_guess;
+    config.ascii |= cli.ascii;
+    Ok((config, warnings))
+}
+
+/// Whether the locale asks for UTF-8, going by the first of `LC_ALL`, `LC_CTYPE` and `LANG` that is
+/// set. Without any of them there's nothing to go on, so UTF-8 is assumed
+fn utf8_locale() -> bool {
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .into_iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|locale| !locale.is_empty())
+        .is_none_or(|locale| {
+            let locale = locale.to_lowercase();
+            locale.contains("utf-8") || locale.contains("utf8")
+        })
+}
+
 fn main() -> Result<()> {
+    let cli = Cli::parse(std::env::args().skip(1))?;
+    match cli.command {
+        Command::Help => {
+            print!("{HELP}");
+            return Ok(());
+        }
+        Command::Version => {
+            println!("termsweeper {}", env!("CARGO_PKG_VERSION"));
+            return Ok(());
+        }
+        Command::WriteConfig => {
+            let path = io::write_default_config(&Config::default_file(), cli.config.as_deref())?;
+            println!("Wrote the default config to {}", path.display());
+            return Ok(());
+        }
+        Command::Play | Command::Leaderboard | Command::Stats => {}
+    }
+    if let Some(data_dir) = cli.data_dir.clone() {
+        io::set_data_dir(data_dir);
+    }
+
+    // Printing commands write to stdout and never take over the terminal
+    if cli.command == Command::Leaderboard {
+        let scores = io::read_leaderboard()?;
+        print!(
+            "{}",
+            report::leaderboard(&scores, cli.difficulty, cli.format)
+        );
+        return Ok(());
+    }
+    if cli.command == Command::Stats {
+        let stats = io::read_stats()?
+            .map(|stats| Stats::parse(&stats))
+            .transpose()?
+            .unwrap_or_default();
+        print!("{}", report::stats(&stats, cli.difficulty, cli.format));
+        return Ok(());
+    }
+
+    // Files used to all live in the config folder, move them to where they belong now. Only
+    // playing moves or rewrites anything, the printing commands read them wherever they are
+    if let Err(err) = io::migrate_old_files() {
+        eprintln!("Couldn't move files out of the old folder: {err}");
+    }
+    if let Err(err) = io::migrate_leaderboard() {
+        eprintln!("Couldn't update the leaderboard: {err}");
+    }
+
+    let (config, warnings) = load_config(&cli)?;
+    // Block characters come out garbled on terminals that aren't expecting UTF-8
+    let suggestion = (!config.ascii && !utf8_locale())
+        .then_some("The locale isn't UTF-8, try --ascii if the board looks garbled");
+    if let Some(suggestion) = suggestion {
+        eprintln!("{suggestion}");
+    }
+
     // Init term ui
     let backend = CrosstermBackend::new(std::io::stderr());
     let terminal = Terminal::new(backend)?;
     let size = terminal.size().unwrap(); // If this fails we might as well exit now
-    let events = EventHandler::new(30);
+    let term_size = (size.width, size.height);
+    if !check_compatible((8, 10), term_size) && !check_compatible((10, 8), term_size) {
+        bail!("The terminal is too small, make it bigger and try again");
+    }
+    if let Some(Difficulty::Custom { width, height, .. }) = cli.difficulty {
+        if !check_compatible((width, height), term_size) {
+            bail!(
+                "A {width}x{height} board doesn't fit in a {}x{} terminal",
+                size.width,
+                size.height
+            );
+        }
+    }
+    let events = EventHandler::new(config.tick_rate);
     let mut tui = Tui::new(terminal, events);
     tui.enter()?;
 
     // Build app object
-    let mut app = App::new(types::Difficulty::Medium, (size.width, size.height));
+    let mut app = App::new(&config, cli.seed, term_size);
+    if let Some(warning) = warnings.first() {
+        app.show_message(format!("Config: {warning}"));
+    } else if let Some(suggestion) = suggestion {
+        app.show_message(suggestion.to_string());
+    }
+
+    // Offer to pick up the game left unfinished last time
+    match io::read_save() {
+        Result::Ok(Some(save)) => app.offer_resume(&save),
+        Result::Ok(None) => {}
+        Err(err) => app.show_message(format!("Couldn't read the saved game: {err}")),
+    }
 
     // Do main program loop
     while !app.should_quit {
@@ -39,5 +176,8 @@ fn main() -> Result<()> {
     // Close down the term ui stuff cleanly
     tui.exit()?;
 
+    // Keep the unfinished game for next time
+    app.save_game()?;
+
     Ok(())
 }
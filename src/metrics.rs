@@ -0,0 +1,207 @@
+use std::{cmp::Ordering, collections::VecDeque, time::Duration};
+
+use crate::{grid::Grid, leaderboard::Score};
+
+/// How well a game was played, only the counts are kept since everything else follows from them
+/// and the time
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Metrics {
+    /// Bechtel's Board Benchmark Value, the fewest clicks that clear the board
+    pub bbbv: usize,
+    /// Every reveal, chord, flag and question mark
+    pub clicks: usize,
+    /// Clicks that changed the board
+    pub effective_clicks: usize,
+}
+
+impl Metrics {
+    /// 3BV per click, 1 is a perfect game
+    #[allow(clippy::cast_precision_loss)]
+    pub fn efficiency(&self) -> f64 {
+        if self.clicks == 0 {
+            0.
+        } else {
+            self.bbbv as f64 / self.clicks as f64
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    pub fn bbbv_per_second(&self, time: Duration) -> f64 {
+        let secs = time.as_secs_f64();
+        if secs > 0. {
+            self.bbbv as f64 / secs
+        } else {
+            0.
+        }
+    }
+
+    /// Time divided by 3BV/s, lower is better
+    pub fn rqp(&self, time: Duration) -> f64 {
+        let speed = self.bbbv_per_second(time);
+        if speed > 0. {
+            time.as_secs_f64() / speed
+        } else {
+            f64::INFINITY
+        }
+    }
+}
+
+/// Works out the 3BV of a layout, every opening takes one click and so does every number that
+/// isn't on the edge of an opening
+pub fn bbbv(mines: &Grid<bool>) -> usize {
+    let is_zero =
+        |x: usize, y: usize| !mines[(x, y)] && mines.count_neighbours(x, y, |&mine| mine) == 0;
+    let mut counted = Grid::new(mines.width(), mines.height(), false);
+    let mut bbbv = 0;
+
+    for (x, y) in mines.positions() {
+        if counted[(x, y)] || !is_zero(x, y) {
+            continue;
+        }
+        bbbv += 1;
+        counted[(x, y)] = true;
+        let mut opening = VecDeque::from([(x, y)]);
+        while let Some((x, y)) = opening.pop_front() {
+            for (x, y) in mines.neighbours(x, y) {
+                if !counted[(x, y)] {
+                    counted[(x, y)] = true;
+                    if is_zero(x, y) {
+                        opening.push_back((x, y));
+                    }
+                }
+            }
+        }
+    }
+
+    bbbv + mines
+        .positions()
+        .filter(|&pos| !mines[pos] && !counted[pos])
+        .count()
+}
+
+/// What the leaderboard can be ordered by, each from best to worst
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ScoreSort {
+    Time,
+    Bbbv,
+    Efficiency,
+    BbbvPerSecond,
+    Rqp,
+}
+
+impl ScoreSort {
+    pub const fn as_static_str(self) -> &'static str {
+        match self {
+            Self::Time => "time",
+            Self::Bbbv => "3BV",
+            Self::Efficiency => "efficiency",
+            Self::BbbvPerSecond => "3BV/s",
+            Self::Rqp => "RQP",
+        }
+    }
+
+    pub const fn next(self) -> Self {
+        match self {
+            Self::Time => Self::Bbbv,
+            Self::Bbbv => Self::Efficiency,
+            Self::Efficiency => Self::BbbvPerSecond,
+            Self::BbbvPerSecond => Self::Rqp,
+            Self::Rqp => Self::Time,
+        }
+    }
+
+    /// The value a score is sorted by, if the score has one
+    pub fn value(self, score: &Score) -> Option<f64> {
+        let time = score.time;
+        match self {
+            Self::Time => Some(time.as_secs_f64()),
+            #[allow(clippy::cast_precision_loss)]
+            Self::Bbbv => score.metrics.map(|metrics| metrics.bbbv as f64),
+            Self::Efficiency => score.metrics.map(|metrics| metrics.efficiency()),
+            Self::BbbvPerSecond => score.metrics.map(|metrics| metrics.bbbv_per_second(time)),
+            Self::Rqp => score.metrics.map(|metrics| metrics.rqp(time)),
+        }
+    }
+
+    /// Orders scores best first, scores from before metrics were recorded go last
+    pub fn compare(self, a: &Score, b: &Score) -> Ordering {
+        match (self.value(a), self.value(b)) {
+            (Some(a), Some(b)) => match self {
+                Self::Time | Self::Rqp => a.total_cmp(&b),
+                Self::Bbbv | Self::Efficiency | Self::BbbvPerSecond => b.total_cmp(&a),
+            },
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => a.time.cmp(&b.time),
+        }
+    }
+
+    /// Formats a value from `value` for the leaderboard
+    pub fn format(self, value: f64) -> String {
+        match self {
+            Self::Time => format!("{value:.3}s"),
+            Self::Bbbv => format!("{value:.0} 3BV"),
+            Self::Efficiency => format!("{:.0}%", value * 100.),
+            Self::BbbvPerSecond => format!("{value:.2} 3BV/s"),
+            Self::Rqp => format!("{value:.1} RQP"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Board, Difficulty, GameState};
+
+    /// A diamond of mines in the corner of an opening. The opening is one click, the 4 in the
+    /// middle of the diamond and the three numbers it cuts off from the opening are one each
+    fn layout() -> Grid<bool> {
+        let mut mines = Grid::new(6, 5, false);
+        for pos in [(4, 2), (3, 3), (5, 3), (4, 4)] {
+            mines[pos] = true;
+        }
+        mines
+    }
+
+    #[test]
+    fn bbbv_counts_openings_and_isolated_numbers() {
+        assert_eq!(bbbv(&layout()), 5);
+        assert_eq!(bbbv(&Grid::new(3, 3, false)), 1);
+    }
+
+    #[test]
+    fn efficiency_comes_from_the_clicks() {
+        let difficulty = Difficulty::Custom {
+            width: 6,
+            height: 5,
+            mines: 4,
+        };
+        let mut board = Board::from_mines(difficulty, 0, &layout(), (200, 100));
+        board.left_click(0, 0);
+        // Clicking an uncovered tile is a click that changes nothing
+        board.left_click(0, 0);
+        for (x, y) in [(5, 2), (4, 3), (3, 4), (5, 4)] {
+            board.left_click(x, y);
+        }
+        assert!(matches!(board.state(), GameState::Won { .. }));
+
+        let metrics = board.metrics();
+        assert_eq!(
+            (metrics.bbbv, metrics.clicks, metrics.effective_clicks),
+            (5, 6, 5)
+        );
+        let time = Duration::from_millis(2500);
+        assert!((metrics.efficiency() - 5. / 6.).abs() < 1e-9);
+        assert!((metrics.bbbv_per_second(time) - 2.).abs() < 1e-9);
+        assert!((metrics.rqp(time) - 1.25).abs() < 1e-9);
+
+        let idle = Metrics {
+            bbbv: 5,
+            clicks: 0,
+            effective_clicks: 0,
+        };
+        assert!(idle.efficiency() == 0.);
+        assert!(idle.bbbv_per_second(Duration::ZERO) == 0.);
+        assert!(idle.rqp(Duration::ZERO).is_infinite());
+    }
+}
@@ -0,0 +1,363 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::{
+    grid::Grid,
+    replay::{Action, Input, Replay},
+    types::{format_seconds, parse_seconds, tile_count, Difficulty},
+};
+
+/// Revision of the RAW video format written on export
+const RAWVF_VERSION: &str = "Rev7";
+/// Size of a tile in pixels, RAW files give mouse positions in pixels as well as tiles
+const TILE_PIXELS: usize = 16;
+
+/// The standard levels, anything else is exported as a custom level
+const LEVELS: [(&str, usize, usize, usize); 3] = [
+    ("Beginner", 9, 9, 10),
+    ("Intermediate", 16, 16, 40),
+    ("Expert", 30, 16, 99),
+];
+
+/// Converts a recorded game to a RAW video file. Question marks are left out since they don't
+/// change the game, games with undone moves can't be represented at all
+pub fn to_rawvf(replay: &Replay) -> Result<String> {
+    let (width, height) = replay.mines.size();
+    let mines = replay.mines.iter().filter(|&&mine| mine).count();
+    let level = LEVELS
+        .iter()
+        .find(|&&(_, level_width, level_height, level_mines)| {
+            (level_width, level_height, level_mines) == (width, height, mines)
+        })
+        .map_or("Custom", |(level, ..)| level);
+
+    let mut lines = vec![
+        format!("RawVF_Version: {RAWVF_VERSION}"),
+        String::from("Program: termsweeper"),
+        format!("Version: {}", env!("CARGO_PKG_VERSION")),
+        format!("Level: {level}"),
+        format!("Width: {width}"),
+        format!("Height: {height}"),
+        format!("Mines: {mines}"),
+        String::from("Marks: Off"),
+        String::from("Board:"),
+    ];
+    for y in 0..height {
+        lines.push(
+            (0..width)
+                .map(|x| if replay.mines[(x, y)] { '*' } else { '0' })
+                .collect(),
+        );
+    }
+
+    lines.push(String::from("Events:"));
+    lines.push(String::from("0.000 start"));
+    for action in &replay.actions {
+        let buttons: &[&str] = match action.input {
+            Input::Reveal => &["lc", "lr"],
+            Input::Flag => &["rc", "rr"],
+            // Chording is done by pressing both buttons and letting go
+            Input::Chord => &["lc", "rc", "lr", "rr"],
+            Input::Question => &[],
+            Input::Undo | Input::Redo => bail!("Games with undone moves can't be exported"),
+        };
+        for button in buttons {
+            lines.push(mouse_event(action, button));
+        }
+    }
+    lines.push(String::new());
+    Ok(lines.join("\n"))
+}
+
+fn mouse_event(action: &Action, button: &str) -> String {
+    format!(
+        "{} {button} {} {} ({} {})",
+        format_seconds(action.time),
+        action.x * TILE_PIXELS + TILE_PIXELS / 2,
+        action.y * TILE_PIXELS + TILE_PIXELS / 2,
+        action.x + 1,
+        action.y + 1
+    )
+}
+
+/// Reads a RAW video file into a replay, mouse events are turned back into clicks on tiles the
+/// way classic Minesweeper handles them
+pub fn parse_rawvf(rawvf: &str) -> Result<Replay> {
+    let mut lines = rawvf.lines().enumerate();
+    let mut header = Vec::new();
+    for (_, line) in lines.by_ref() {
+        if line.trim() == "Board:" {
+            break;
+        }
+        if let Some((key, value)) = line.split_once(':') {
+            header.push((key.trim(), value.trim()));
+        }
+    }
+    let value = |key: &str| -> Result<usize> {
+        let (_, value) = header
+            .iter()
+            .find(|(header_key, _)| *header_key == key)
+            .ok_or_else(|| anyhow!("Missing '{key}'"))?;
+        value
+            .parse()
+            .map_err(|_| anyhow!("Invalid '{key}': {value}"))
+    };
+    let (width, height) = (value("Width")?, value("Height")?);
+    // Check the size before anything is allocated for the board
+    if width == 0 || height == 0 {
+        bail!("The board has no tiles");
+    }
+    tile_count(width, height)?;
+
+    let mines = parse_board(&mut lines, (width, height))?;
+    let mine_count = mines.iter().filter(|&&mine| mine).count();
+    if value("Mines")? != mine_count {
+        bail!(
+            "The board has {mine_count} mines rather than {}",
+            value("Mines")?
+        );
+    }
+    if !lines.any(|(_, line)| line.trim() == "Events:") {
+        bail!("Missing events");
+    }
+
+    let actions = parse_events(lines.map(|(_, line)| line), (width, height));
+
+    Ok(Replay {
+        difficulty: Difficulty::Custom {
+            width,
+            height,
+            mines: mine_count,
+        },
+        seed: 0,
+        mines,
+        actions,
+    })
+}
+
+/// Reads the rows of the board, each line comes with its index in the file
+fn parse_board<'a>(
+    lines: &mut impl Iterator<Item = (usize, &'a str)>,
+    (width, height): (usize, usize),
+) -> Result<Grid<bool>> {
+    let mut mines = Grid::new(width, height, false);
+    for y in 0..height {
+        let (number, row) = lines
+            .next()
+            .ok_or_else(|| anyhow!("The board has fewer than {height} rows"))?;
+        let row: Vec<char> = row.trim().chars().collect();
+        if row.len() != width {
+            bail!("Line {}: expected {width} tiles", number + 1);
+        }
+        for (x, char) in row.into_iter().enumerate() {
+            mines[(x, y)] = match char {
+                '*' => true,
+                '0' | '.' => false,
+                _ => bail!(
+                    "Line {}, column {}: unknown tile '{char}'",
+                    number + 1,
+                    x + 1
+                ),
+            };
+        }
+    }
+    Ok(mines)
+}
+
+/// Turns mouse events into the clicks classic Minesweeper would make of them
+fn parse_events<'a>(
+    lines: impl Iterator<Item = &'a str>,
+    (width, height): (usize, usize),
+) -> Vec<Action> {
+    let mut actions = Vec::new();
+    let (mut left, mut right) = (false, false);
+    for line in lines {
+        let Some(event) = MouseEvent::parse(line, (width, height)) else {
+            continue;
+        };
+        let input = match event.button {
+            "lc" => {
+                left = true;
+                None
+            }
+            "rc" => {
+                right = true;
+                // Right pressed alone flags straight away, with left held it starts a chord
+                (!left).then_some(Input::Flag)
+            }
+            "lr" => {
+                left = false;
+                Some(if right { Input::Chord } else { Input::Reveal })
+            }
+            "rr" => {
+                right = false;
+                left.then_some(Input::Chord)
+            }
+            "mr" => Some(Input::Chord),
+            _ => None,
+        };
+        // A chord ends once both buttons are released, the first release triggers it
+        if input == Some(Input::Chord) {
+            (left, right) = (false, false);
+        }
+        let Some(input) = input else {
+            continue;
+        };
+        // Letting go outside of the board doesn't click anything
+        let Some((x, y)) = event.tile else {
+            continue;
+        };
+        actions.push(Action {
+            time: event.time,
+            input,
+            x,
+            y,
+        });
+    }
+    actions
+}
+
+/// A mouse event line, `time button x y (column row)` with pixels and tiles counted from 1
+struct MouseEvent<'a> {
+    time: Duration,
+    button: &'a str,
+    tile: Option<(usize, usize)>,
+}
+
+impl<'a> MouseEvent<'a> {
+    fn parse(line: &'a str, (width, height): (usize, usize)) -> Option<Self> {
+        let mut parts = line.split_whitespace();
+        let time = parse_time(parts.next()?)?;
+        let button = parts.next()?;
+        let pixel_x: usize = parts.next()?.parse().ok()?;
+        let pixel_y: usize = parts.next()?.parse().ok()?;
+        let column = parts.next().and_then(|column| column.strip_prefix('('));
+        let row = parts.next().and_then(|row| row.strip_suffix(')'));
+        let position = match (column, row) {
+            (Some(column), Some(row)) => column
+                .parse::<usize>()
+                .ok()?
+                .checked_sub(1)
+                .zip(row.parse::<usize>().ok()?.checked_sub(1)),
+            _ => Some((pixel_x / TILE_PIXELS, pixel_y / TILE_PIXELS)),
+        }
+        .filter(|&(x, y)| x < width && y < height);
+        Some(Self {
+            time,
+            button,
+            tile: position,
+        })
+    }
+}
+
+/// Reads a time in seconds with up to millisecond precision, events from before the game started
+/// have negative times and count as the start
+fn parse_time(time: &str) -> Option<Duration> {
+    if let Some(time) = time.strip_prefix('-') {
+        return parse_time(time).map(|_| Duration::ZERO);
+    }
+    parse_seconds(time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Board;
+
+    /// A game won on a 6x6 board with a wall of mines down the middle
+    fn replay() -> Replay {
+        let mut mines = Grid::new(6, 6, false);
+        for y in 0..5 {
+            mines[(3, y)] = true;
+        }
+        let difficulty = Difficulty::Custom {
+            width: 6,
+            height: 6,
+            mines: 5,
+        };
+        let mut board = Board::from_mines(difficulty, 0, &mines, (200, 100));
+        board.left_click(0, 0);
+        board.right_click(3, 0);
+        board.left_click(5, 5);
+        board.right_click(3, 4);
+        board.do_control_click(2, 5);
+        Replay::from_board(&board)
+    }
+
+    fn clicks(replay: &Replay) -> Vec<(&'static str, usize, usize)> {
+        replay
+            .actions
+            .iter()
+            .map(|action| (action.input.as_static_str(), action.x, action.y))
+            .collect()
+    }
+
+    /// The exported video with one line swapped for another
+    fn edited(find: &str, replace: &str) -> String {
+        let rawvf = to_rawvf(&replay()).unwrap();
+        assert!(rawvf.contains(find));
+        rawvf.replacen(find, replace, 1)
+    }
+
+    fn parse_error(rawvf: &str) -> String {
+        parse_rawvf(rawvf).err().unwrap().to_string()
+    }
+
+    #[test]
+    fn export_round_trips() {
+        let replay = replay();
+        let parsed = parse_rawvf(&to_rawvf(&replay).unwrap()).unwrap();
+        assert!(parsed.mines == replay.mines);
+        assert_eq!(parsed.mines.size(), (6, 6));
+        assert_eq!(
+            clicks(&parsed),
+            [
+                ("reveal", 0, 0),
+                ("flag", 3, 0),
+                ("reveal", 5, 5),
+                ("flag", 3, 4),
+                ("chord", 2, 5)
+            ]
+        );
+        assert_eq!(clicks(&parsed), clicks(&replay));
+    }
+
+    #[test]
+    fn rows_must_have_the_width() {
+        let rawvf = edited("\n000*00\n", "\n000*0\n");
+        assert_eq!(parse_error(&rawvf), "Line 10: expected 6 tiles");
+    }
+
+    #[test]
+    fn tiles_must_be_known() {
+        let rawvf = edited("\n000*00\n", "\n000*0x\n");
+        assert_eq!(parse_error(&rawvf), "Line 10, column 6: unknown tile 'x'");
+    }
+
+    #[test]
+    fn mine_count_must_match() {
+        let rawvf = edited("Mines: 5", "Mines: 6");
+        assert_eq!(parse_error(&rawvf), "The board has 5 mines rather than 6");
+    }
+
+    #[test]
+    fn events_are_required() {
+        let rawvf = to_rawvf(&replay()).unwrap();
+        let (board, _) = rawvf.split_once("Events:").unwrap();
+        assert_eq!(parse_error(board), "Missing events");
+    }
+
+    #[test]
+    fn huge_boards_are_rejected_before_allocating() {
+        let rawvf = edited("Width: 6", "Width: 100000").replacen("Height: 6", "Height: 100000", 1);
+        assert_eq!(parse_error(&rawvf), "A 100000x100000 board is too big");
+        let rawvf = edited("Width: 6", &format!("Width: {}", usize::MAX));
+        assert_eq!(
+            parse_error(&rawvf),
+            format!("A {}x6 board is too big", usize::MAX)
+        );
+        let rawvf = edited("Height: 6", "Height: 0");
+        assert_eq!(parse_error(&rawvf), "The board has no tiles");
+    }
+}
@@ -0,0 +1,321 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::{
+    grid::Grid,
+    layout::{layout_to_lines, parse_layout},
+    types::{Board, Difficulty},
+};
+
+/// Version of the replay file format, replays written by other versions are rejected
+const REPLAY_VERSION: u32 = 1;
+const REPLAY_HEADER: &str = "termsweeper replay";
+
+/// Playback speeds that can be picked, as multiples of real time
+pub const PLAYBACK_SPEEDS: [u32; 3] = [1, 2, 4];
+
+/// Something the player did to the board
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Input {
+    Reveal,
+    Chord,
+    Flag,
+    Question,
+    Undo,
+    Redo,
+}
+
+impl Input {
+    const ALL: [Self; 6] = [
+        Self::Reveal,
+        Self::Chord,
+        Self::Flag,
+        Self::Question,
+        Self::Undo,
+        Self::Redo,
+    ];
+
+    pub const fn as_static_str(self) -> &'static str {
+        match self {
+            Self::Reveal => "reveal",
+            Self::Chord => "chord",
+            Self::Flag => "flag",
+            Self::Question => "question",
+            Self::Undo => "undo",
+            Self::Redo => "redo",
+        }
+    }
+
+    pub fn parse(str: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|input| input.as_static_str() == str)
+    }
+}
+
+/// An input along with where and when it happened, the time is counted from the first move
+#[derive(Clone, Copy)]
+pub struct Action {
+    pub time: Duration,
+    pub input: Input,
+    pub x: usize,
+    pub y: usize,
+}
+
+impl Action {
+    pub fn as_string(&self) -> String {
+        format!(
+            "{} {} {} {}",
+            self.time.as_millis(),
+            self.input.as_static_str(),
+            self.x,
+            self.y
+        )
+    }
+
+    pub fn parse(str: &str) -> Option<Self> {
+        let mut parts = str.split(' ');
+        let action = Self {
+            time: Duration::from_millis(parts.next()?.parse().ok()?),
+            input: Input::parse(parts.next()?)?,
+            x: parts.next()?.parse().ok()?,
+            y: parts.next()?.parse().ok()?,
+        };
+        parts.next().is_none().then_some(action)
+    }
+}
+
+/// Everything needed to play a game back, the layout is the one the first click settled on
+pub struct Replay {
+    pub difficulty: Difficulty,
+    pub seed: u64,
+    pub mines: Grid<bool>,
+    pub actions: Vec<Action>,
+}
+
+impl Replay {
+    pub fn from_board(board: &Board) -> Self {
+        Self {
+            difficulty: board.difficulty,
+            seed: board.seed(),
+            mines: board.mine_layout(),
+            actions: board.actions().to_vec(),
+        }
+    }
+
+    pub fn as_string(&self) -> String {
+        let mut lines = vec![
+            format!("{REPLAY_HEADER} {REPLAY_VERSION}"),
+            format!("difficulty: {}", self.difficulty.as_string()),
+            format!("seed: {}", self.seed),
+            String::from("mines:"),
+        ];
+        lines.extend(layout_to_lines(&self.mines));
+        lines.push(String::from("actions:"));
+        lines.extend(self.actions.iter().map(Action::as_string));
+        lines.push(String::new());
+        lines.join("\n")
+    }
+
+    pub fn parse(replay: &str) -> Result<Self> {
+        let mut lines = replay.lines();
+        let version = lines
+            .next()
+            .and_then(|header| header.strip_prefix(REPLAY_HEADER))
+            .ok_or_else(|| anyhow!("Not a replay file"))?;
+        if version.trim() != REPLAY_VERSION.to_string() {
+            bail!("Replay file version {} isn't supported", version.trim());
+        }
+        let difficulty = lines
+            .next()
+            .and_then(|line| line.strip_prefix("difficulty: "))
+            .and_then(Difficulty::parse)
+            .ok_or_else(|| anyhow!("Invalid difficulty"))?;
+        let seed = lines
+            .next()
+            .and_then(|line| line.strip_prefix("seed: "))
+            .and_then(|seed| seed.parse().ok())
+            .ok_or_else(|| anyhow!("Invalid seed"))?;
+        if lines.next() != Some("mines:") {
+            bail!("Missing mine layout");
+        }
+
+        // The layout starts on the fifth line of the file
+        let mines = parse_layout(lines.by_ref().take_while(|line| *line != "actions:"), 5)?;
+        // A board is built for the difficulty when the replay is played, so it has to agree with
+        // the layout
+        if !difficulty.allows_size(mines.size()) {
+            bail!("The mine layout doesn't match the difficulty");
+        }
+        if mines.iter().filter(|&&mine| mine).count() != difficulty.mines() {
+            bail!("The mine layout has the wrong number of mines");
+        }
+
+        let actions = lines
+            .filter(|line| !line.is_empty())
+            .map(|line| Action::parse(line).ok_or_else(|| anyhow!("Invalid action '{line}'")))
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            difficulty,
+            seed,
+            mines,
+            actions,
+        })
+    }
+}
+
+/// A replay being played back onto a board
+pub struct Playback {
+    replay: Replay,
+    /// Index of the next action to apply
+    next: usize,
+    /// How far into the game the playback is
+    clock: Duration,
+    last_tick: Instant,
+    speed: u32,
+    pub paused: bool,
+}
+
+impl Playback {
+    /// Starts playing the replay back, along with the board it should be played on
+    pub fn new(replay: Replay, max_render_size: (u16, u16)) -> (Self, Board) {
+        let board = Board::from_mines(
+            replay.difficulty,
+            replay.seed,
+            &replay.mines,
+            max_render_size,
+        );
+        let playback = Self {
+            replay,
+            next: 0,
+            clock: Duration::ZERO,
+            last_tick: Instant::now(),
+            speed: PLAYBACK_SPEEDS[0],
+            paused: false,
+        };
+        (playback, board)
+    }
+
+    pub const fn clock(&self) -> Duration {
+        self.clock
+    }
+
+    pub const fn speed(&self) -> u32 {
+        self.speed
+    }
+
+    pub const fn set_speed(&mut self, speed: u32) {
+        self.speed = speed;
+    }
+
+    pub const fn finished(&self) -> bool {
+        self.next >= self.replay.actions.len()
+    }
+
+    /// Moves the clock on by the time since the last tick and applies every action that is due
+    pub fn tick(&mut self, board: &mut Board) {
+        let now = Instant::now();
+        if !self.paused && !self.finished() {
+            self.clock += now.duration_since(self.last_tick) * self.speed;
+        }
+        self.last_tick = now;
+        while let Some(action) = self.replay.actions.get(self.next) {
+            if action.time > self.clock {
+                break;
+            }
+            board.apply(*action);
+            self.next += 1;
+        }
+    }
+
+    /// Pauses the playback and applies the next action straight away
+    pub fn step(&mut self, board: &mut Board) {
+        self.paused = true;
+        if let Some(action) = self.replay.actions.get(self.next) {
+            self.clock = self.clock.max(action.time);
+            board.apply(*action);
+            self.next += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replay() -> String {
+        let mut board = Board::with_seed(Difficulty::Easy, (200, 100), 1);
+        board.left_click(0, 0);
+        board.right_click(9, 7);
+        Replay::from_board(&board).as_string()
+    }
+
+    fn parse_error(replay: &str) -> String {
+        Replay::parse(replay).err().unwrap().to_string()
+    }
+
+    #[test]
+    fn replay_round_trips() {
+        let replay = replay();
+        let parsed = Replay::parse(&replay).unwrap();
+        assert!(parsed.difficulty == Difficulty::Easy);
+        assert_eq!(parsed.seed, 1);
+        assert_eq!(parsed.mines.size(), (10, 8));
+        assert_eq!(parsed.actions.len(), 2);
+        assert_eq!(parsed.as_string(), replay);
+    }
+
+    #[test]
+    fn malformed_lines_are_rejected() {
+        let replay = replay();
+        assert_eq!(
+            parse_error(&replay.replace("termsweeper replay 1", "termsweeper replay 9")),
+            "Replay file version 9 isn't supported"
+        );
+        assert_eq!(
+            parse_error(&replay.replace("seed: 1", "seed: one")),
+            "Invalid seed"
+        );
+        assert_eq!(
+            parse_error(&replay.replace("mines:\n", "")),
+            "Missing mine layout"
+        );
+        assert_eq!(
+            parse_error(&replay.replacen("..........", "....x.....", 1)),
+            "Line 5, column 5: expected '*' or '.' but found 'x'"
+        );
+        assert_eq!(
+            parse_error(&replay.replace(" flag ", " fly ")),
+            format!(
+                "Invalid action '{}'",
+                replay.lines().last().unwrap().replace("flag", "fly")
+            )
+        );
+    }
+
+    #[test]
+    fn layout_must_match_the_difficulty() {
+        let replay = replay();
+        assert_eq!(
+            parse_error(&replay.replace("difficulty: Easy", "difficulty: Hard")),
+            "The mine layout doesn't match the difficulty"
+        );
+        assert_eq!(
+            parse_error(&replay.replace("difficulty: Easy", "difficulty: Custom 30000x30000/10")),
+            "The mine layout doesn't match the difficulty"
+        );
+        assert_eq!(
+            parse_error(&replay.replace("difficulty: Easy", "difficulty: Custom 10x8/11")),
+            "The mine layout has the wrong number of mines"
+        );
+    }
+
+    #[test]
+    fn huge_layouts_are_rejected_before_allocating() {
+        let row = ".".repeat(2000);
+        let layout = format!("{row}\n").repeat(600);
+        let replay = format!("termsweeper replay 1\ndifficulty: Easy\nseed: 1\nmines:\n{layout}");
+        assert_eq!(parse_error(&replay), "A 2000x600 board is too big");
+    }
+}
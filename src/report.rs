@@ -0,0 +1,350 @@
+use std::fmt::Write;
+
+use crate::{
+    leaderboard::{format_date, Score},
+    metrics::ScoreSort,
+    stats::Stats,
+    types::{format_seconds, Difficulty},
+};
+
+/// How the `leaderboard` and `stats` commands print their data
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Columns lined up for reading in a terminal
+    Table,
+    /// An array with one object per row
+    Json,
+    /// A header line followed by one line per row
+    Csv,
+}
+
+impl Format {
+    pub fn parse(str: &str) -> Option<Self> {
+        match str {
+            "table" => Some(Self::Table),
+            "json" => Some(Self::Json),
+            "csv" => Some(Self::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// A field of a row, kept typed so JSON can tell numbers from text
+enum Value {
+    Text(String),
+    Number(String),
+    Bool(bool),
+    Missing,
+}
+
+impl Value {
+    fn number(number: usize) -> Self {
+        Self::Number(number.to_string())
+    }
+
+    fn as_table(&self) -> String {
+        match self {
+            Self::Text(str) | Self::Number(str) => str.clone(),
+            Self::Bool(bool) => String::from(if *bool { "yes" } else { "no" }),
+            Self::Missing => String::from("-"),
+        }
+    }
+
+    fn as_json(&self) -> String {
+        match self {
+            Self::Text(str) => json_string(str),
+            Self::Number(str) => str.clone(),
+            Self::Bool(bool) => bool.to_string(),
+            Self::Missing => String::from("null"),
+        }
+    }
+
+    fn as_csv(&self) -> String {
+        match self {
+            Self::Text(str) if str.contains([',', '"', '\n', '\r']) => {
+                format!("\"{}\"", str.replace('"', "\"\""))
+            }
+            Self::Text(str) | Self::Number(str) => str.clone(),
+            Self::Bool(bool) => bool.to_string(),
+            Self::Missing => String::new(),
+        }
+    }
+}
+
+fn json_string(str: &str) -> String {
+    let mut json = String::from('"');
+    for char in str.chars() {
+        match char {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            char if char.is_control() => {
+                let _ = write!(json, "\\u{:04x}", u32::from(char));
+            }
+            char => json.push(char),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// Writes rows out in the format asked for, each column has a JSON key and a table heading
+fn render(format: Format, columns: &[(&str, &str)], rows: &[Vec<Value>]) -> String {
+    let mut lines = Vec::new();
+    match format {
+        Format::Table => {
+            let cells: Vec<Vec<String>> = rows
+                .iter()
+                .map(|row| row.iter().map(Value::as_table).collect())
+                .collect();
+            let widths: Vec<usize> = columns
+                .iter()
+                .enumerate()
+                .map(|(index, (_, heading))| {
+                    cells
+                        .iter()
+                        .map(|row| row[index].chars().count())
+                        .chain([heading.len()])
+                        .max()
+                        .unwrap_or_default()
+                })
+                .collect();
+            let line = |row: Vec<String>| {
+                row.iter()
+                    .zip(&widths)
+                    .map(|(cell, width)| format!("{cell:width$}"))
+                    .collect::<Vec<_>>()
+                    .join("  ")
+                    .trim_end()
+                    .to_string()
+            };
+            lines.push(line(
+                columns
+                    .iter()
+                    .map(|(_, heading)| (*heading).to_string())
+                    .collect(),
+            ));
+            lines.extend(cells.into_iter().map(line));
+        }
+        Format::Json => {
+            lines.push(String::from("["));
+            for (index, row) in rows.iter().enumerate() {
+                let fields: Vec<String> = columns
+                    .iter()
+                    .zip(row)
+                    .map(|((key, _), value)| format!("\"{key}\": {}", value.as_json()))
+                    .collect();
+                let comma = if index + 1 < rows.len() { "," } else { "" };
+                lines.push(format!("  {{{}}}{comma}", fields.join(", ")));
+            }
+            lines.push(String::from("]"));
+        }
+        Format::Csv => {
+            lines.push(
+                columns
+                    .iter()
+                    .map(|(key, _)| *key)
+                    .collect::<Vec<_>>()
+                    .join(","),
+            );
+            lines.extend(
+                rows.iter()
+                    .map(|row| row.iter().map(Value::as_csv).collect::<Vec<_>>().join(",")),
+            );
+        }
+    }
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+/// Every score, optionally for one difficulty, fastest first within each difficulty
+pub fn leaderboard(scores: &[Score], difficulty: Option<Difficulty>, format: Format) -> String {
+    let mut scores: Vec<&Score> = scores
+        .iter()
+        .filter(|score| difficulty.is_none_or(|difficulty| score.difficulty == difficulty))
+        .collect();
+    scores.sort_by(|a, b| {
+        a.difficulty
+            .cmp(&b.difficulty)
+            .then((a.hints > 0).cmp(&(b.hints > 0)))
+            .then_with(|| ScoreSort::Time.compare(a, b))
+    });
+
+    let mut rank = 0;
+    let rows: Vec<Vec<Value>> = scores
+        .iter()
+        .enumerate()
+        .map(|(index, score)| {
+            // Hinted scores are ranked on their own leaderboard
+            let previous = index.checked_sub(1).map(|index| scores[index]);
+            rank = match previous {
+                Some(previous)
+                    if previous.difficulty == score.difficulty
+                        && (previous.hints > 0) == (score.hints > 0) =>
+                {
+                    rank + 1
+                }
+                _ => 1,
+            };
+            score_row(rank, score)
+        })
+        .collect();
+    render(
+        format,
+        &[
+            ("rank", "Rank"),
+            ("difficulty", "Difficulty"),
+            ("time", "Time"),
+            ("name", "Name"),
+            ("date", "Date"),
+            ("width", "Width"),
+            ("height", "Height"),
+            ("mines", "Mines"),
+            ("seed", "Seed"),
+            ("hints", "Hints"),
+            ("no_guess", "No guess"),
+            ("bbbv", "3BV"),
+            ("clicks", "Clicks"),
+            ("effective_clicks", "Effective"),
+            ("efficiency", "Efficiency"),
+            ("bbbv_per_second", "3BV/s"),
+        ],
+        &rows,
+    )
+}
+
+fn score_row(rank: usize, score: &Score) -> Vec<Value> {
+    let optional = |value: Option<String>| value.map_or(Value::Missing, Value::Number);
+    let metric = |value: Option<f64>, precision: usize| {
+        optional(value.map(|value| format!("{value:.precision$}")))
+    };
+    vec![
+        Value::number(rank),
+        Value::Text(score.difficulty.as_string()),
+        Value::Number(format_seconds(score.time)),
+        score.name.clone().map_or(Value::Missing, Value::Text),
+        score
+            .date
+            .map_or(Value::Missing, |date| Value::Text(format_date(date))),
+        optional(score.size.map(|(width, _)| width.to_string())),
+        optional(score.size.map(|(_, height)| height.to_string())),
+        Value::number(score.mines),
+        optional(score.seed.map(|seed| seed.to_string())),
+        Value::number(score.hints),
+        Value::Bool(score.no_guess),
+        optional(score.metrics.map(|metrics| metrics.bbbv.to_string())),
+        optional(score.metrics.map(|metrics| metrics.clicks.to_string())),
+        optional(
+            score
+                .metrics
+                .map(|metrics| metrics.effective_clicks.to_string()),
+        ),
+        metric(score.metrics.map(|metrics| metrics.efficiency()), 3),
+        metric(
+            score
+                .metrics
+                .map(|metrics| metrics.bbbv_per_second(score.time)),
+            3,
+        ),
+    ]
+}
+
+/// Stats for every difficulty that has been played, optionally just one of them
+pub fn stats(stats: &Stats, difficulty: Option<Difficulty>, format: Format) -> String {
+    let rows: Vec<Vec<Value>> = stats
+        .iter()
+        .filter(|(played, _)| difficulty.is_none_or(|difficulty| **played == difficulty))
+        .map(|(difficulty, stats)| {
+            vec![
+                Value::Text(difficulty.as_string()),
+                Value::number(stats.started),
+                Value::number(stats.won),
+                Value::number(stats.lost),
+                Value::number(stats.abandoned),
+                stats
+                    .win_rate()
+                    .map_or(Value::Missing, |rate| Value::Number(format!("{rate:.1}"))),
+                Value::number(stats.streak),
+                Value::number(stats.best_streak),
+                stats
+                    .average_win_time()
+                    .map_or(Value::Missing, |time| Value::Number(format_seconds(time))),
+            ]
+        })
+        .collect();
+    render(
+        format,
+        &[
+            ("difficulty", "Difficulty"),
+            ("started", "Started"),
+            ("won", "Won"),
+            ("lost", "Lost"),
+            ("abandoned", "Abandoned"),
+            ("win_rate", "Win rate %"),
+            ("streak", "Streak"),
+            ("best_streak", "Best"),
+            ("average_win_time", "Avg win"),
+        ],
+        &rows,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn named(name: &str) -> Score {
+        Score {
+            difficulty: Difficulty::Easy,
+            time: Duration::from_millis(12_500),
+            hints: 0,
+            metrics: None,
+            name: Some(String::from(name)),
+            date: None,
+            size: None,
+            mines: 10,
+            seed: None,
+            no_guess: false,
+            first_click: None,
+        }
+    }
+
+    #[test]
+    fn json_strings_are_escaped() {
+        assert_eq!(
+            json_string("say \"hi\"\\\n\t\u{1}"),
+            r#""say \"hi\"\\\n\t\u0001""#
+        );
+        let json = leaderboard(&[named("a \"b\"\nc")], None, Format::Json);
+        assert!(json.contains(r#""name": "a \"b\"\nc""#), "{json}");
+        assert!(json.contains(r#""width": null"#), "{json}");
+    }
+
+    #[test]
+    fn csv_quotes_names_that_need_it() {
+        let scores = [
+            named("plain"),
+            named("a,b"),
+            named("say \"hi\""),
+            named("two\nlines"),
+        ];
+        let csv = leaderboard(&scores, None, Format::Csv);
+        let names: Vec<String> = scores
+            .iter()
+            .map(|score| Value::Text(score.name.clone().unwrap()).as_csv())
+            .collect();
+        assert_eq!(
+            names,
+            ["plain", "\"a,b\"", "\"say \"\"hi\"\"\"", "\"two\nlines\""]
+        );
+        for name in &names {
+            assert!(csv.contains(&format!(",{name},")), "{csv}");
+        }
+        // A header and one line per score, plus the line break inside the quoted name
+        assert_eq!(csv.lines().count(), 1 + scores.len() + 1);
+    }
+}
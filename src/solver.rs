@@ -0,0 +1,916 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    grid::Grid,
+    types::{Board, Tile, TileState},
+};
+
+/// Most backtracking steps spent enumerating a single group of constrained tiles before the
+/// solver gives up on exact probabilities
+const ENUMERATION_LIMIT: usize = 200_000;
+
+/// What a player can see of a single tile
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Cell {
+    Hidden,
+    Flagged,
+    Revealed(usize),
+}
+
+/// The visible state of a board, this is all the solver gets to work with
+#[derive(PartialEq, Eq)]
+pub struct View {
+    cells: Grid<Cell>,
+    mines: usize,
+}
+
+impl View {
+    pub fn new(size: (usize, usize), mines: usize) -> Self {
+        Self {
+            cells: Grid::new(size.0, size.1, Cell::Hidden),
+            mines,
+        }
+    }
+
+    pub fn from_board(board: &Board) -> Self {
+        let size = board.get_board_size();
+        let mut view = Self::new(size, board.mine_count());
+        for (x, y) in view.cells.positions() {
+            let tile = board.tile(x, y).unwrap();
+            let cell = match tile.tile_state() {
+                TileState::Visible => Cell::Revealed(tile.bombs_near()),
+                TileState::Marked => Cell::Flagged,
+                TileState::Hidden | TileState::Question => Cell::Hidden,
+            };
+            view.set(x, y, cell);
+        }
+        view
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, cell: Cell) {
+        self.cells[(x, y)] = cell;
+    }
+}
+
+/// A group of tiles known to contain exactly `mines` mines
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct Constraint {
+    cells: Vec<usize>,
+    mines: usize,
+}
+
+impl Constraint {
+    fn is_subset_of(&self, other: &Self) -> bool {
+        self.cells.len() < other.cells.len()
+            && self
+                .cells
+                .iter()
+                .all(|cell| other.cells.binary_search(cell).is_ok())
+    }
+}
+
+/// Everything the solver could work out from a view of the board
+pub struct Analysis {
+    /// Tiles that can't be mines
+    pub safe: Vec<(usize, usize)>,
+    /// Tiles that must be mines
+    pub mines: Vec<(usize, usize)>,
+    probabilities: Grid<Option<f64>>,
+}
+
+impl Analysis {
+    /// Chance of the tile being a mine, `None` for tiles that are already uncovered
+    pub fn probability(&self, x: usize, y: usize) -> Option<f64> {
+        self.probabilities.get(x, y).copied().flatten()
+    }
+}
+
+/// Works out which hidden tiles are safe, which are mines and how likely every other hidden tile
+/// is to be a mine. Flags are treated as hidden tiles, so a misplaced flag can't lead the solver
+/// to a wrong conclusion
+pub fn analyse(view: &View) -> Analysis {
+    let cells = &view.cells;
+    let mut known: Vec<Option<bool>> = vec![None; cells.len()];
+    let mut constraints = Vec::new();
+    for (x, y) in cells.positions() {
+        if let Cell::Revealed(bombs_near) = cells[(x, y)] {
+            known[cells.index(x, y)] = Some(false);
+            let mut unknown: Vec<usize> = cells
+                .neighbours(x, y)
+                .filter(|&pos| !matches!(cells[pos], Cell::Revealed(_)))
+                .map(|(nx, ny)| cells.index(nx, ny))
+                .collect();
+            unknown.sort_unstable();
+            if !unknown.is_empty() && bombs_near <= unknown.len() {
+                constraints.push(Constraint {
+                    cells: unknown,
+                    mines: bombs_near,
+                });
+            }
+        }
+    }
+
+    propagate(&mut known, &mut constraints);
+    let probabilities = enumerate(view, &mut known, &constraints);
+
+    let mut analysis = Analysis {
+        safe: Vec::new(),
+        mines: Vec::new(),
+        probabilities: Grid::new(cells.width(), cells.height(), None),
+    };
+    for (index, probability) in probabilities.into_iter().enumerate() {
+        let (x, y) = cells.coords(index);
+        if matches!(cells[(x, y)], Cell::Revealed(_)) {
+            continue;
+        }
+        match known[index] {
+            Some(true) => analysis.mines.push((x, y)),
+            Some(false) => analysis.safe.push((x, y)),
+            None => {}
+        }
+        analysis.probabilities[(x, y)] = Some(match known[index] {
+            Some(true) => 1.,
+            Some(false) => 0.,
+            None => probability,
+        });
+    }
+    analysis
+}
+
+/// Removes tiles with a known state from the constraints
+fn reduce(known: &[Option<bool>], constraints: &mut Vec<Constraint>) {
+    for constraint in constraints.iter_mut() {
+        let mines = constraint
+            .cells
+            .iter()
+            .filter(|&&cell| known[cell] == Some(true))
+            .count();
+        constraint.mines = constraint.mines.saturating_sub(mines);
+        constraint.cells.retain(|&cell| known[cell].is_none());
+    }
+    constraints.retain(|constraint| !constraint.cells.is_empty());
+    constraints.sort_unstable_by(|a, b| a.cells.cmp(&b.cells));
+    constraints.dedup();
+}
+
+/// Applies the single tile rules and subset reasoning until nothing else can be deduced
+fn propagate(known: &mut [Option<bool>], constraints: &mut Vec<Constraint>) {
+    loop {
+        reduce(known, constraints);
+
+        // If a tile shows as many mines as it has unknown neighbours they are all mines, and if it
+        // shows none they are all safe
+        let mut progress = false;
+        for constraint in constraints.iter() {
+            let is_mine = if constraint.mines == 0 {
+                false
+            } else if constraint.mines == constraint.cells.len() {
+                true
+            } else {
+                continue;
+            };
+            for &cell in &constraint.cells {
+                known[cell] = Some(is_mine);
+            }
+            progress = true;
+        }
+        if progress {
+            continue;
+        }
+
+        // If one group of tiles is entirely inside of another, then the tiles only in the larger
+        // group contain the difference in mines
+        let mut containing: HashMap<usize, Vec<&Constraint>> = HashMap::new();
+        for constraint in constraints.iter() {
+            for &cell in &constraint.cells {
+                containing.entry(cell).or_default().push(constraint);
+            }
+        }
+        let existing: HashSet<&Constraint> = constraints.iter().collect();
+        let mut derived = Vec::new();
+        for small in constraints.iter() {
+            for &large in &containing[&small.cells[0]] {
+                if small.is_subset_of(large) && large.mines >= small.mines {
+                    let cells: Vec<usize> = large
+                        .cells
+                        .iter()
+                        .copied()
+                        .filter(|cell| small.cells.binary_search(cell).is_err())
+                        .collect();
+                    let constraint = Constraint {
+                        cells,
+                        mines: large.mines - small.mines,
+                    };
+                    if !existing.contains(&constraint) && !derived.contains(&constraint) {
+                        derived.push(constraint);
+                    }
+                }
+            }
+        }
+        if derived.is_empty() {
+            return;
+        }
+        constraints.append(&mut derived);
+    }
+}
+
+/// All the possible mine layouts of one connected group of constrained tiles, counted by how many
+/// mines each layout uses
+struct Component {
+    cells: Vec<usize>,
+    /// Number of layouts using each number of mines
+    layouts: Vec<f64>,
+    /// For every cell, the number of layouts using each number of mines that have a mine there
+    cell_layouts: Vec<Vec<f64>>,
+}
+
+/// Splits the constraints into groups that don't share any tiles
+fn split_components(constraints: &[Constraint], tiles: usize) -> Vec<(Vec<usize>, Vec<usize>)> {
+    fn find(parent: &mut [usize], mut index: usize) -> usize {
+        while parent[index] != index {
+            parent[index] = parent[parent[index]];
+            index = parent[index];
+        }
+        index
+    }
+
+    let mut parent: Vec<usize> = (0..constraints.len()).collect();
+    let mut owner: Vec<Option<usize>> = vec![None; tiles];
+    for (index, constraint) in constraints.iter().enumerate() {
+        for &cell in &constraint.cells {
+            if let Some(other) = owner[cell] {
+                let (a, b) = (find(&mut parent, index), find(&mut parent, other));
+                parent[a] = b;
+            } else {
+                owner[cell] = Some(index);
+            }
+        }
+    }
+
+    let mut groups: Vec<(Vec<usize>, Vec<usize>)> = Vec::new();
+    let mut group_of_root = vec![None; constraints.len()];
+    for index in 0..constraints.len() {
+        let root = find(&mut parent, index);
+        let group = *group_of_root[root].get_or_insert_with(|| {
+            groups.push((Vec::new(), Vec::new()));
+            groups.len() - 1
+        });
+        groups[group].1.push(index);
+    }
+    for (cell, owner) in owner.into_iter().enumerate() {
+        if let Some(index) = owner {
+            let root = find(&mut parent, index);
+            groups[group_of_root[root].unwrap()].0.push(cell);
+        }
+    }
+    groups
+}
+
+/// Backtracking search over every mine layout of a group that satisfies its constraints
+struct Search<'a> {
+    constraints: Vec<&'a Constraint>,
+    /// Constraints each local cell takes part in
+    cell_constraints: Vec<Vec<usize>>,
+    assigned: Vec<bool>,
+    mines_placed: Vec<usize>,
+    unassigned: Vec<usize>,
+    steps: usize,
+    component: Component,
+}
+
+impl Search<'_> {
+    fn run(&mut self, cell: usize, mines: usize) -> bool {
+        self.steps += 1;
+        if self.steps > ENUMERATION_LIMIT {
+            return false;
+        }
+        if cell == self.component.cells.len() {
+            if self.component.layouts.len() <= mines {
+                self.component.layouts.resize(mines + 1, 0.);
+            }
+            self.component.layouts[mines] += 1.;
+            for (index, &is_mine) in self.assigned.iter().enumerate() {
+                if is_mine {
+                    let counts = &mut self.component.cell_layouts[index];
+                    if counts.len() <= mines {
+                        counts.resize(mines + 1, 0.);
+                    }
+                    counts[mines] += 1.;
+                }
+            }
+            return true;
+        }
+        for is_mine in [false, true] {
+            let fits = self.cell_constraints[cell].iter().all(|&index| {
+                let placed = self.mines_placed[index] + usize::from(is_mine);
+                let remaining = self.unassigned[index] - 1;
+                placed <= self.constraints[index].mines
+                    && placed + remaining >= self.constraints[index].mines
+            });
+            if !fits {
+                continue;
+            }
+            for &index in &self.cell_constraints[cell] {
+                self.mines_placed[index] += usize::from(is_mine);
+                self.unassigned[index] -= 1;
+            }
+            self.assigned[cell] = is_mine;
+            let finished = self.run(cell + 1, mines + usize::from(is_mine));
+            for &index in &self.cell_constraints[cell] {
+                self.mines_placed[index] -= usize::from(is_mine);
+                self.unassigned[index] += 1;
+            }
+            self.assigned[cell] = false;
+            if !finished {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Enumerates every mine layout of a group of tiles, returns `None` if there are too many to go
+/// through
+fn enumerate_component(
+    cells: Vec<usize>,
+    constraint_indices: &[usize],
+    constraints: &[Constraint],
+) -> Option<Component> {
+    let constraints: Vec<&Constraint> = constraint_indices
+        .iter()
+        .map(|&index| &constraints[index])
+        .collect();
+    let cell_constraints = cells
+        .iter()
+        .map(|cell| {
+            constraints
+                .iter()
+                .enumerate()
+                .filter(|(_, constraint)| constraint.cells.binary_search(cell).is_ok())
+                .map(|(index, _)| index)
+                .collect()
+        })
+        .collect();
+    let unassigned = constraints
+        .iter()
+        .map(|constraint| constraint.cells.len())
+        .collect();
+    let mut search = Search {
+        mines_placed: vec![0; constraints.len()],
+        constraints,
+        cell_constraints,
+        assigned: vec![false; cells.len()],
+        unassigned,
+        steps: 0,
+        component: Component {
+            cell_layouts: vec![Vec::new(); cells.len()],
+            layouts: Vec::new(),
+            cells,
+        },
+    };
+    if !search.run(0, 0) {
+        return None;
+    }
+
+    // Scale the counts down so huge numbers of layouts can't overflow later on
+    let mut component = search.component;
+    let max = component.layouts.iter().copied().fold(0., f64::max);
+    if max > 0. {
+        for count in component
+            .layouts
+            .iter_mut()
+            .chain(component.cell_layouts.iter_mut().flatten())
+        {
+            *count /= max;
+        }
+    }
+    Some(component)
+}
+
+/// Combines two distributions of mine counts
+fn convolve(a: &[f64], b: &[f64]) -> Vec<f64> {
+    if a.is_empty() || b.is_empty() {
+        return Vec::new();
+    }
+    let mut result = vec![0.; a.len() + b.len() - 1];
+    for (i, a) in a.iter().enumerate() {
+        for (j, b) in b.iter().enumerate() {
+            result[i + j] += a * b;
+        }
+    }
+    let max = result.iter().copied().fold(0., f64::max);
+    if max > 0. {
+        for count in &mut result {
+            *count /= max;
+        }
+    }
+    result
+}
+
+/// Rough chances used when there are too many layouts to count, constrained tiles get the density
+/// of the densest constraint they are part of and the leftover mines are spread over the rest
+#[allow(clippy::cast_precision_loss)]
+fn estimate(
+    constraints: &[Constraint],
+    interior: &[usize],
+    remaining_mines: usize,
+    probabilities: &mut [f64],
+) {
+    for constraint in constraints {
+        let density = constraint.mines as f64 / constraint.cells.len() as f64;
+        for &cell in &constraint.cells {
+            probabilities[cell] = f64::max(probabilities[cell], density);
+        }
+    }
+    let frontier_mines: f64 = constraints
+        .iter()
+        .map(|constraint| constraint.mines as f64)
+        .sum::<f64>()
+        .min(remaining_mines as f64);
+    let density = if interior.is_empty() {
+        0.
+    } else {
+        ((remaining_mines as f64 - frontier_mines) / interior.len() as f64).clamp(0., 1.)
+    };
+    for &cell in interior {
+        probabilities[cell] = density;
+    }
+}
+
+/// Relative number of ways to place the mines left over from the constrained tiles into the
+/// `free` unconstrained ones, indexed by the number of mines in the constrained tiles
+#[allow(clippy::cast_precision_loss)]
+fn leftover_weights(max_frontier_mines: usize, remaining_mines: usize, free: usize) -> Vec<f64> {
+    let mut log_weights = vec![f64::NEG_INFINITY; max_frontier_mines + 1];
+    let mut log_choose = 0.;
+    let mut previous = None;
+    for (frontier_mines, weight) in log_weights.iter_mut().enumerate().rev() {
+        let Some(left) = remaining_mines.checked_sub(frontier_mines) else {
+            continue;
+        };
+        if left > free {
+            break;
+        }
+        if let Some(previous) = previous {
+            // C(free, left) = C(free, left - 1) * (free - left + 1) / left
+            log_choose += ((free - previous) as f64 / left as f64).ln();
+        }
+        previous = Some(left);
+        *weight = log_choose;
+    }
+    let max = log_weights
+        .iter()
+        .copied()
+        .fold(f64::NEG_INFINITY, f64::max);
+    log_weights
+        .into_iter()
+        .map(|weight| (weight - max).exp())
+        .collect()
+}
+
+/// Works out the chances for the tiles of one group, `rest` is the combined layouts of every other
+/// group. The counts are scaled integers, so comparing them for equality is exact
+fn weigh_component(
+    component: &Component,
+    rest: &[f64],
+    weights: &[f64],
+    known: &mut [Option<bool>],
+    probabilities: &mut [f64],
+) {
+    // Weight of each mine count in this group once every other group is accounted for
+    let factors: Vec<f64> = (0..component.layouts.len())
+        .map(|mines| {
+            rest.iter()
+                .enumerate()
+                .map(|(other_mines, layouts)| {
+                    layouts * weights.get(mines + other_mines).copied().unwrap_or(0.)
+                })
+                .sum()
+        })
+        .collect();
+    let total: f64 = component
+        .layouts
+        .iter()
+        .zip(&factors)
+        .map(|(a, b)| a * b)
+        .sum();
+    if total <= 0. {
+        return;
+    }
+    let possible: Vec<usize> = (0..component.layouts.len())
+        .filter(|&mines| component.layouts[mines] * factors[mines] > 0.)
+        .collect();
+    for (&cell, counts) in component.cells.iter().zip(&component.cell_layouts) {
+        let weighted: f64 = counts.iter().zip(&factors).map(|(a, b)| a * b).sum();
+        probabilities[cell] = (weighted / total).clamp(0., 1.);
+        let count = |mines: usize| counts.get(mines).copied().unwrap_or(0.);
+        if possible.iter().all(|&mines| count(mines) <= 0.) {
+            known[cell] = Some(false);
+        } else if possible
+            .iter()
+            .all(|&mines| count(mines) >= component.layouts[mines])
+        {
+            known[cell] = Some(true);
+        }
+    }
+}
+
+/// Works out the chance of each tile being a mine by going through every layout of the
+/// constrained tiles, weighting each by how many ways the remaining mines fit in the unconstrained
+/// tiles. Tiles that turn out to be a mine or safe in every layout are recorded in `known`
+#[allow(clippy::cast_precision_loss)]
+fn enumerate(view: &View, known: &mut [Option<bool>], constraints: &[Constraint]) -> Vec<f64> {
+    let tiles = known.len();
+    let mut probabilities = vec![0.; tiles];
+    let known_mines = known.iter().filter(|&&known| known == Some(true)).count();
+    let remaining_mines = view.mines.saturating_sub(known_mines);
+
+    let mut components = Vec::new();
+    let mut exact = true;
+    let mut frontier = vec![false; tiles];
+    for (cells, constraint_indices) in split_components(constraints, tiles) {
+        for &cell in &cells {
+            frontier[cell] = true;
+        }
+        match enumerate_component(cells, &constraint_indices, constraints) {
+            Some(component) => components.push(component),
+            None => exact = false,
+        }
+    }
+    let interior: Vec<usize> = (0..tiles)
+        .filter(|&cell| known[cell].is_none() && !frontier[cell])
+        .collect();
+    if !exact {
+        estimate(constraints, &interior, remaining_mines, &mut probabilities);
+        return probabilities;
+    }
+
+    // Layouts of every group before and after each group, so the layouts of all the other groups
+    // can be found without combining everything again for every group
+    let mut before = vec![vec![1.]];
+    for component in &components {
+        before.push(convolve(before.last().unwrap(), &component.layouts));
+    }
+    let mut after = vec![vec![1.]];
+    for component in components.iter().rev() {
+        after.push(convolve(after.last().unwrap(), &component.layouts));
+    }
+    after.reverse();
+    let all_layouts = before.last().unwrap();
+
+    let free = interior.len();
+    let weights = leftover_weights(all_layouts.len() - 1, remaining_mines, free);
+    let total: f64 = all_layouts.iter().zip(&weights).map(|(a, b)| a * b).sum();
+    if total <= 0. || total.is_nan() {
+        return probabilities;
+    }
+
+    // Interior tiles share the leftover mines evenly
+    let weighted_left = all_layouts
+        .iter()
+        .zip(&weights)
+        .enumerate()
+        .map(|(frontier_mines, (layouts, weight))| {
+            layouts * weight * remaining_mines.saturating_sub(frontier_mines) as f64
+        })
+        .sum::<f64>();
+    let mut left_over = (0..all_layouts.len())
+        .filter(|&mines| all_layouts[mines] * weights[mines] > 0.)
+        .map(|mines| remaining_mines.saturating_sub(mines));
+    let interior_known = if left_over.clone().all(|left| left == 0) {
+        Some(false)
+    } else if left_over.all(|left| left == free) {
+        Some(true)
+    } else {
+        None
+    };
+    for &cell in &interior {
+        probabilities[cell] = weighted_left / total / free as f64;
+        known[cell] = interior_known;
+    }
+
+    for (index, component) in components.iter().enumerate() {
+        let rest = convolve(&before[index], &after[index + 1]);
+        weigh_component(component, &rest, &weights, known, &mut probabilities);
+    }
+    probabilities
+}
+
+/// A game played out by the solver, it knows the layout so it can uncover tiles but only ever
+/// makes decisions based on what a player could see
+struct Simulation<'a> {
+    tiles: &'a Grid<Tile>,
+    view: View,
+    revealed_count: usize,
+}
+
+impl<'a> Simulation<'a> {
+    fn new(tiles: &'a Grid<Tile>) -> Self {
+        let mines = tiles.iter().filter(|tile| tile.is_mine()).count();
+        Self {
+            tiles,
+            view: View::new(tiles.size(), mines),
+            revealed_count: 0,
+        }
+    }
+
+    fn reveal(&mut self, x: usize, y: usize) {
+        let mut to_reveal = vec![(x, y)];
+        while let Some((x, y)) = to_reveal.pop() {
+            if matches!(self.view.cells[(x, y)], Cell::Revealed(_)) {
+                continue;
+            }
+            let bombs_near = self.tiles[(x, y)].bombs_near();
+            self.view.set(x, y, Cell::Revealed(bombs_near));
+            self.revealed_count += 1;
+            if bombs_near == 0 {
+                to_reveal.extend(self.tiles.neighbours(x, y));
+            }
+        }
+    }
+
+    /// Applies the single tile rules everywhere once, returns if anything changed. This is a lot
+    /// cheaper than a full analysis and gets through most of a board on its own
+    fn step(&mut self) -> bool {
+        let mut progress = false;
+        for (x, y) in self.tiles.positions() {
+            let Cell::Revealed(bombs_near) = self.view.cells[(x, y)] else {
+                continue;
+            };
+            let (mut flagged, mut hidden) = (0, Vec::new());
+            for pos in self.view.cells.neighbours(x, y) {
+                match self.view.cells[pos] {
+                    Cell::Flagged => flagged += 1,
+                    Cell::Hidden => hidden.push(pos),
+                    Cell::Revealed(_) => {}
+                }
+            }
+            if hidden.is_empty() {
+                continue;
+            }
+            if bombs_near == flagged {
+                for (nx, ny) in hidden {
+                    self.reveal(nx, ny);
+                }
+                progress = true;
+            } else if bombs_near == flagged + hidden.len() {
+                for (nx, ny) in hidden {
+                    self.view.set(nx, ny, Cell::Flagged);
+                }
+                progress = true;
+            }
+        }
+        progress
+    }
+
+    /// Runs a full analysis, flags every tile it proves is a mine and uncovers every tile it proves
+    /// safe, returns if anything changed. A new flag is progress too, the single tile rules can
+    /// build on it
+    fn deep_step(&mut self) -> bool {
+        let analysis = analyse(&self.view);
+        let mut progress = !analysis.safe.is_empty();
+        for &(x, y) in &analysis.mines {
+            progress |= self.view.cells[(x, y)] != Cell::Flagged;
+            self.view.set(x, y, Cell::Flagged);
+        }
+        for &(x, y) in &analysis.safe {
+            self.reveal(x, y);
+        }
+        progress
+    }
+}
+
+/// Checks if every safe tile on the board can be uncovered from the starting click without ever
+/// having to guess
+pub fn is_solvable(tiles: &Grid<Tile>, start: (usize, usize)) -> bool {
+    if tiles[start].is_mine() {
+        return false;
+    }
+    let safe_tiles = tiles.iter().filter(|tile| !tile.is_mine()).count();
+
+    let mut simulation = Simulation::new(tiles);
+    simulation.reveal(start.0, start.1);
+    while simulation.revealed_count < safe_tiles {
+        if !simulation.step() && !simulation.deep_step() {
+            return false;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every position of a board, row by row
+    fn positions(width: usize, height: usize) -> impl Iterator<Item = (usize, usize)> {
+        (0..height).flat_map(move |y| (0..width).map(move |x| (x, y)))
+    }
+
+    const fn touches((x, y): (usize, usize), (nx, ny): (usize, usize)) -> bool {
+        x.abs_diff(nx) <= 1 && y.abs_diff(ny) <= 1
+    }
+
+    /// Builds a view from a picture of the board, `*` is a covered mine, `#` a covered safe tile
+    /// and `.` an uncovered tile showing the number of mines around it
+    fn view(rows: &[&str]) -> View {
+        let layout: Vec<&[u8]> = rows.iter().map(|row| row.as_bytes()).collect();
+        let (width, height) = (layout[0].len(), layout.len());
+        let mines: Vec<(usize, usize)> = positions(width, height)
+            .filter(|&(x, y)| layout[y][x] == b'*')
+            .collect();
+        let mut view = View::new((width, height), mines.len());
+        for (x, y) in positions(width, height) {
+            if layout[y][x] == b'.' {
+                let bombs_near = mines.iter().filter(|&&mine| touches(mine, (x, y))).count();
+                view.set(x, y, Cell::Revealed(bombs_near));
+            }
+        }
+        view
+    }
+
+    /// Builds the tiles of a board from a picture of it, `*` is a mine and anything else is safe
+    fn tiles(rows: &[&str]) -> Grid<Tile> {
+        let (width, height) = (rows[0].len(), rows.len());
+        let mut mines = Grid::new(width, height, false);
+        for (x, y) in positions(width, height) {
+            mines[(x, y)] = rows[y].as_bytes()[x] == b'*';
+        }
+        let difficulty = crate::types::Difficulty::Custom {
+            width,
+            height,
+            mines: mines.iter().filter(|&&mine| mine).count(),
+        };
+        let board = Board::from_mines(difficulty, 0, &mines, (u16::MAX, u16::MAX));
+        let mut tiles = Grid::new(width, height, Tile::default());
+        for (x, y) in positions(width, height) {
+            tiles[(x, y)] = board.tile(x, y).unwrap().clone();
+        }
+        tiles
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{a} isn't {b}");
+    }
+
+    #[test]
+    fn single_tile_rules_find_mines_and_safe_tiles() {
+        let analysis = analyse(&view(&["##*##", "#####", ".....", "*...."]));
+        assert!(analysis.mines == [(0, 3)]);
+        assert!(analysis.safe == [(0, 1), (1, 1), (2, 1), (3, 1), (4, 1)]);
+        // The one mine left is somewhere in the top row
+        assert_close(analysis.probability(2, 0).unwrap(), 0.2);
+        assert!(analysis.probability(1, 2).is_none());
+    }
+
+    #[test]
+    fn subset_rule_solves_one_two_one() {
+        // Five covered tiles under 1 1 2 1 1, no single number gives anything away
+        let mut known = vec![None; 5];
+        let mut constraints = [
+            vec![0, 1],
+            vec![0, 1, 2],
+            vec![1, 2, 3],
+            vec![2, 3, 4],
+            vec![3, 4],
+        ]
+        .into_iter()
+        .zip([1, 1, 2, 1, 1])
+        .map(|(cells, mines)| Constraint { cells, mines })
+        .collect();
+        propagate(&mut known, &mut constraints);
+        assert!(
+            known
+                == [
+                    Some(false),
+                    Some(true),
+                    Some(false),
+                    Some(true),
+                    Some(false)
+                ]
+        );
+
+        let analysis = analyse(&view(&["*####", "#*#*#", "....."]));
+        assert!(analysis.mines == [(1, 1), (3, 1)]);
+        assert!(analysis.safe == [(0, 1), (2, 1), (4, 1)]);
+    }
+
+    #[test]
+    fn subset_rule_solves_one_one_against_a_wall() {
+        let analysis = analyse(&view(&["#*#", "*##", "..#"]));
+        assert!(analysis.mines.is_empty());
+        assert!(analysis.safe == [(2, 1), (2, 2)]);
+        assert_close(analysis.probability(0, 1).unwrap(), 0.5);
+        assert_close(analysis.probability(1, 1).unwrap(), 0.5);
+    }
+
+    #[test]
+    fn mine_count_resolves_what_the_numbers_cant() {
+        // The 1 has a mine on one side or the other, so the tile on the end has none left for it
+        let analysis = analyse(&view(&["*.##"]));
+        assert!(analysis.mines.is_empty());
+        assert!(analysis.safe == [(3, 0)]);
+        assert_close(analysis.probability(0, 0).unwrap(), 0.5);
+
+        // With a second mine the tile on the end has to have it
+        let analysis = analyse(&view(&["*.#*"]));
+        assert!(analysis.mines == [(3, 0)]);
+        assert!(analysis.safe.is_empty());
+    }
+
+    #[test]
+    fn probabilities_add_up_to_the_mines() {
+        let analysis = analyse(&view(&["##*###", "######", "#..#*#", "#..###", "*####*"]));
+        let total: f64 = positions(6, 5)
+            .filter_map(|(x, y)| analysis.probability(x, y))
+            .sum();
+        assert_close(total, 4.);
+    }
+
+    #[test]
+    fn groups_that_share_no_tiles_are_split() {
+        // The two ends of `*.##.*`, the numbers share no covered tiles
+        let constraints = [
+            Constraint {
+                cells: vec![0, 2],
+                mines: 1,
+            },
+            Constraint {
+                cells: vec![3, 5],
+                mines: 1,
+            },
+        ];
+        let mut groups = split_components(&constraints, 6);
+        groups.sort();
+        assert!(groups == [(vec![0, 2], vec![0]), (vec![3, 5], vec![1])]);
+    }
+
+    #[test]
+    fn convolve_combines_counts() {
+        // One group with 0 or 1 mines and one with 0, 1 or 2, scaled so the largest count is 1
+        let combined = convolve(&[1., 1.], &[1., 2., 1.]);
+        for (a, b) in combined.into_iter().zip([1. / 3., 1., 1., 1. / 3.]) {
+            assert_close(a, b);
+        }
+        assert!(convolve(&[], &[1.]).is_empty());
+    }
+
+    #[test]
+    fn enumeration_gives_up_on_huge_groups() {
+        // A grid of numbers with covered tiles between them, all tied into one group with far too
+        // many layouts to go through
+        let size = 21;
+        let is_number = |(x, y): (usize, usize)| x % 2 == 1 && y % 2 == 1;
+        let is_mine = |(x, y): (usize, usize)| !is_number((x, y)) && (x * 7 + y * 3) % 5 == 0;
+        let constraints: Vec<Constraint> = positions(size, size)
+            .filter(|&pos| is_number(pos))
+            .map(|pos| {
+                let around: Vec<(usize, usize)> = positions(size, size)
+                    .filter(|&other| other != pos && touches(pos, other))
+                    .collect();
+                Constraint {
+                    cells: around.iter().map(|&(x, y)| y * size + x).collect(),
+                    mines: around.iter().filter(|&&other| is_mine(other)).count(),
+                }
+            })
+            .collect();
+        let groups = split_components(&constraints, size * size);
+        assert!(groups.len() == 1);
+        let (cells, indices) = groups.into_iter().next().unwrap();
+        assert!(enumerate_component(cells, &indices, &constraints).is_none());
+
+        // The estimate still gives every covered tile a chance
+        let rows: Vec<String> = (0..size)
+            .map(|y| {
+                (0..size)
+                    .map(|x| match (is_number((x, y)), is_mine((x, y))) {
+                        (true, _) => '.',
+                        (false, true) => '*',
+                        (false, false) => '#',
+                    })
+                    .collect()
+            })
+            .collect();
+        let rows: Vec<&str> = rows.iter().map(String::as_str).collect();
+        let analysis = analyse(&view(&rows));
+        for (x, y) in positions(size, size) {
+            if let Some(probability) = analysis.probability(x, y) {
+                assert!((0. ..=1.).contains(&probability));
+            }
+        }
+    }
+
+    #[test]
+    fn mine_deductions_are_progress() {
+        // The 1 can't tell which side its mine is on, only the mine count puts the other one on
+        // the end. Finding it is still progress, and finding it again isn't
+        let tiles = tiles(&["*.#*"]);
+        let mut simulation = Simulation::new(&tiles);
+        simulation.reveal(1, 0);
+        assert!(!simulation.step());
+        assert!(simulation.deep_step());
+        assert!(simulation.view.cells[(3, 0)] == Cell::Flagged);
+        assert!(!simulation.deep_step());
+        assert!(!is_solvable(&tiles, (1, 0)));
+    }
+}
@@ -0,0 +1,174 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use anyhow::{bail, Result};
+
+use crate::types::Difficulty;
+
+/// How games on one difficulty have gone
+#[derive(Clone, Copy, Default)]
+pub struct DifficultyStats {
+    pub started: usize,
+    pub won: usize,
+    pub lost: usize,
+    /// Games left for a new one before they were won or lost
+    pub abandoned: usize,
+    pub streak: usize,
+    pub best_streak: usize,
+    /// Sum of the times of every win, for the average
+    pub win_time: Duration,
+}
+
+impl DifficultyStats {
+    pub fn average_win_time(&self) -> Option<Duration> {
+        u32::try_from(self.won)
+            .ok()
+            .filter(|&won| won > 0)
+            .map(|won| self.win_time / won)
+    }
+
+    /// Percentage of games that were won, abandoned games count against it
+    #[allow(clippy::cast_precision_loss)]
+    pub fn win_rate(&self) -> Option<f64> {
+        let finished = self.won + self.lost + self.abandoned;
+        (finished > 0).then(|| self.won as f64 / finished as f64 * 100.)
+    }
+
+    fn as_string(&self) -> String {
+        format!(
+            "{} started, {} won, {} lost, {} abandoned, {} streak, {} best, {} ms",
+            self.started,
+            self.won,
+            self.lost,
+            self.abandoned,
+            self.streak,
+            self.best_streak,
+            self.win_time.as_millis()
+        )
+    }
+
+    fn parse(str: &str) -> Option<Self> {
+        let mut stats = Self::default();
+        for field in str.split(", ") {
+            let (count, name) = field.split_once(' ')?;
+            let count = count.parse().ok()?;
+            match name {
+                "started" => stats.started = count,
+                "won" => stats.won = count,
+                "lost" => stats.lost = count,
+                "abandoned" => stats.abandoned = count,
+                "streak" => stats.streak = count,
+                "best" => stats.best_streak = count,
+                "ms" => stats.win_time = Duration::from_millis(count.try_into().ok()?),
+                _ => return None,
+            }
+        }
+        Some(stats)
+    }
+}
+
+/// Statistics for every difficulty that has been played
+#[derive(Default)]
+pub struct Stats {
+    difficulties: BTreeMap<Difficulty, DifficultyStats>,
+}
+
+impl Stats {
+    pub fn iter(&self) -> impl Iterator<Item = (&Difficulty, &DifficultyStats)> {
+        self.difficulties.iter()
+    }
+
+    fn entry(&mut self, difficulty: Difficulty) -> &mut DifficultyStats {
+        self.difficulties.entry(difficulty).or_default()
+    }
+
+    pub fn start(&mut self, difficulty: Difficulty) {
+        self.entry(difficulty).started += 1;
+    }
+
+    pub fn win(&mut self, difficulty: Difficulty, time: Duration) {
+        let stats = self.entry(difficulty);
+        stats.won += 1;
+        stats.win_time += time;
+        stats.streak += 1;
+        stats.best_streak = stats.best_streak.max(stats.streak);
+    }
+
+    pub fn lose(&mut self, difficulty: Difficulty) {
+        let stats = self.entry(difficulty);
+        stats.lost += 1;
+        stats.streak = 0;
+    }
+
+    pub fn abandon(&mut self, difficulty: Difficulty) {
+        let stats = self.entry(difficulty);
+        stats.abandoned += 1;
+        stats.streak = 0;
+    }
+
+    /// One line per difficulty, `<difficulty>: <count> <name>, ...`
+    pub fn as_string(&self) -> String {
+        let mut lines: Vec<String> = self
+            .difficulties
+            .iter()
+            .map(|(difficulty, stats)| format!("{}: {}", difficulty.as_string(), stats.as_string()))
+            .collect();
+        lines.push(String::new());
+        lines.join("\n")
+    }
+
+    /// Reads stats written by `as_string`, a line that can't be read is an error so the file isn't
+    /// overwritten with less than it had
+    pub fn parse(str: &str) -> Result<Self> {
+        let mut difficulties = BTreeMap::new();
+        for (number, line) in str.lines().enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            let parsed = line.split_once(": ").and_then(|(difficulty, stats)| {
+                Some((
+                    Difficulty::parse(difficulty)?,
+                    DifficultyStats::parse(stats)?,
+                ))
+            });
+            let Some((difficulty, stats)) = parsed else {
+                bail!("Line {}: couldn't read '{line}'", number + 1);
+            };
+            difficulties.insert(difficulty, stats);
+        }
+        Ok(Self { difficulties })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_round_trip() {
+        let mut stats = Stats::default();
+        stats.start(Difficulty::Easy);
+        stats.win(Difficulty::Easy, Duration::from_millis(12_345));
+        stats.start(Difficulty::Hard);
+        stats.abandon(Difficulty::Hard);
+        let parsed = Stats::parse(&stats.as_string()).unwrap();
+        assert_eq!(parsed.as_string(), stats.as_string());
+        let easy = parsed.difficulties[&Difficulty::Easy];
+        assert_eq!((easy.started, easy.won, easy.streak), (1, 1, 1));
+        assert_eq!(easy.win_time, Duration::from_millis(12_345));
+    }
+
+    #[test]
+    fn unreadable_lines_are_errors() {
+        let stats = "Easy: 1 started, 1 won, 0 lost, 0 abandoned, 1 streak, 1 best, 9000 ms\n";
+        assert!(Stats::parse(stats).is_ok());
+        let error = |str: &str| Stats::parse(str).err().unwrap().to_string();
+        assert_eq!(
+            error(&format!("{stats}Easy: 1 started, 1 wins\n")),
+            "Line 2: couldn't read 'Easy: 1 started, 1 wins'"
+        );
+        assert_eq!(
+            error(&format!("Tricky: 1 started\n{stats}")),
+            "Line 1: couldn't read 'Tricky: 1 started'"
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use ratatui::{
     prelude::{Alignment, Rect},
     style::Stylize,
@@ -6,10 +8,34 @@ use ratatui::{
 };
 
 use crate::{
-    io::{read_leaderboard, write_leaderboard},
-    types::{Board, Difficulty, Score, TileState},
+    config::{Config, KeyBindings},
+    io::{
+        board_path, player_name, rawvf_path, read_board, read_leaderboard, read_rawvf, read_replay,
+        read_stats, remove_save, write_board, write_leaderboard, write_rawvf, write_replay,
+        write_save, write_stats,
+    },
+    layout::{layout_to_lines, parse_layout},
+    leaderboard::Score,
+    metrics::{Metrics, ScoreSort},
+    rawvf::{parse_rawvf, to_rawvf},
+    replay::{Playback, Replay},
+    solver::{analyse, Analysis, View},
+    stats::Stats,
+    theme::{Glyphs, Theme},
+    types::{
+        check_compatible, format_seconds, Board, Difficulty, FirstClickPolicy, GameState, SavedGame,
+    },
 };
 
+/// The part of a custom difficulty currently being edited in the difficulty menu
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CustomField {
+    Width,
+    Height,
+    Mines,
+}
+
+#[allow(clippy::struct_excessive_bools)]
 pub struct App {
     pub should_quit: bool,
     board: Board,
@@ -17,53 +43,175 @@ pub struct App {
     last_click_pos: (usize, usize),
     leaderboard_updated: bool,
     leaderboard: Vec<Score>,
+    leaderboard_sort: ScoreSort,
+    stats: Stats,
+    /// Set when the stats file couldn't be read, it is then left alone rather than overwritten
+    stats_unreadable: bool,
+    /// Whether the current game has been counted as started in the stats
+    game_counted: bool,
+    /// Whether the current game has been counted as won or lost in the stats. A loss that is
+    /// undone stays counted, so whatever the game ends in after that isn't
+    result_counted: bool,
+    pub show_stats: bool,
+    /// Metrics and time of the last finished game for the post game summary
+    last_game: Option<(Metrics, Duration)>,
     pub change_difficulty: bool,
+    custom_difficulty: Difficulty,
+    custom_field: CustomField,
+    /// Digits typed so far while entering a seed, `None` when the seed prompt is closed
+    pub seed_input: Option<String>,
+    /// Replay being watched, the board belongs to the replay while this is set
+    playback: Option<Playback>,
+    /// Game from the last session waiting for the player to choose whether to resume it
+    resume_prompt: Option<SavedGame>,
+    /// Feedback shown under the board, like the result of the last hint request
+    message: Option<String>,
+    heatmap: bool,
+    /// Analysis shown by the heatmap along with the view of the board it was worked out from
+    heatmap_analysis: Option<(View, Analysis)>,
+    /// Whether the heatmap was turned on at any point during the current game
+    heatmap_used: bool,
+    no_guess: bool,
+    first_click: FirstClickPolicy,
+    keys: KeyBindings,
+    /// Whether middle clicks mark tiles with question marks
+    question_marks: bool,
+    theme: Theme,
+    glyphs: Glyphs,
     term_size: (u16, u16),
 }
 
 impl App {
-    pub fn new(difficulty: Difficulty, term_size: (u16, u16)) -> Self {
-        let mut leaderboard = read_leaderboard().unwrap_or_default();
-        leaderboard.sort_unstable_by_key(Score::time);
+    /// Starts on a board dealt by `seed` if there is one
+    pub fn new(config: &Config, seed: Option<u64>, term_size: (u16, u16)) -> Self {
+        let (mut leaderboard, leaderboard_error) = match read_leaderboard() {
+            Ok(leaderboard) => (leaderboard, None),
+            Err(err) => (
+                Vec::new(),
+                Some(format!("Couldn't read the leaderboard: {err}")),
+            ),
+        };
+        leaderboard.sort_unstable_by_key(|score| score.time);
+        let difficulty = config.difficulty.clamp_to(term_size);
+        let mut board = seed.map_or_else(
+            || Board::new(difficulty, term_size),
+            |seed| Board::with_seed(difficulty, term_size, seed),
+        );
+        board.first_click = config.first_click;
+        board.no_guess = config.no_guess;
+        let stats =
+            read_stats().and_then(|stats| stats.map(|stats| Stats::parse(&stats)).transpose());
+        let (stats, stats_error) = match stats {
+            Ok(stats) => (stats.unwrap_or_default(), None),
+            Err(err) => (
+                Stats::default(),
+                Some(format!(
+                    "Couldn't read the stats, they won't be saved: {err}"
+                )),
+            ),
+        };
         Self {
             should_quit: false,
-            board: Board::new(difficulty, term_size),
+            board,
             board_rect: Rect::default(),
             last_click_pos: (0, 0),
             leaderboard_updated: false,
             leaderboard,
+            leaderboard_sort: ScoreSort::Time,
+            stats,
+            stats_unreadable: stats_error.is_some(),
+            game_counted: false,
+            result_counted: false,
+            show_stats: false,
+            last_game: None,
             change_difficulty: false,
+            custom_difficulty: Difficulty::Custom {
+                width: 16,
+                height: 16,
+                mines: 40,
+            }
+            .clamp_to(term_size),
+            custom_field: CustomField::Width,
+            seed_input: None,
+            playback: None,
+            resume_prompt: None,
+            message: stats_error.or(leaderboard_error),
+            heatmap: false,
+            heatmap_analysis: None,
+            heatmap_used: false,
+            no_guess: config.no_guess,
+            first_click: config.first_click,
+            keys: config.keys.clone(),
+            question_marks: config.question_marks,
+            theme: config.theme,
+            glyphs: Glyphs::new(config.ascii),
             term_size,
         }
     }
 
     pub fn tick(&mut self) {
-        if self.board.last_move_time().is_some() && !self.leaderboard_updated {
-            if self.board.check_all_mine_state(TileState::Marked) {
-                let time = self
-                    .board
-                    .last_move_time()
-                    .unwrap()
-                    .duration_since(self.board.first_move_time().unwrap());
+        if let Some(playback) = self.playback.as_mut() {
+            playback.tick(&mut self.board);
+        }
+        let state = self.board.state();
+        if self.playback.is_none() {
+            self.update_stats(state);
+        }
+        if state.is_over() && !self.leaderboard_updated && self.playback.is_none() {
+            let _ = write_replay(&Replay::from_board(&self.board).as_string());
+            let metrics = self.board.metrics();
+            self.last_game = Some((metrics, state.elapsed()));
+            // Games played with the heatmap were assisted too much to count for anything
+            if matches!(state, GameState::Won { .. })
+                && !self.heatmap_used
+                && !self.board.is_practice()
+            {
                 self.leaderboard
-                    .push(Score::new(self.board.difficulty, time));
-                self.leaderboard.sort_unstable_by_key(Score::time);
+                    .push(Score::from_board(&self.board, player_name()));
+                self.leaderboard.sort_unstable_by_key(|score| score.time);
                 self.leaderboard.dedup();
                 let _ = write_leaderboard(&self.leaderboard);
             }
             self.leaderboard_updated = true;
         }
 
-        if self.board.last_move_time().is_some()
-            && !self.board.check_all_mine_state(TileState::Marked)
-            && !self.board.check_all_mine_state(TileState::Visible)
-        {
+        self.update_heatmap();
+
+        if matches!(state, GameState::Lost { .. }) {
             self.board.do_game_over_animation();
         } else {
             self.board.clear_fire();
         }
     }
 
+    /// Counts the game as started once the first move is made and as won or lost once it is over
+    fn update_stats(&mut self, state: GameState) {
+        let difficulty = self.board.difficulty;
+        let mut changed = !self.game_counted && state != GameState::NotStarted;
+        if changed {
+            self.stats.start(difficulty);
+            self.game_counted = true;
+        }
+        if !self.result_counted {
+            match state {
+                GameState::Won { .. } => self.stats.win(difficulty, state.elapsed()),
+                GameState::Lost { .. } => self.stats.lose(difficulty),
+                GameState::NotStarted | GameState::Playing { .. } => {}
+            }
+            self.result_counted = state.is_over();
+            changed |= state.is_over();
+        }
+        if changed {
+            self.save_stats();
+        }
+    }
+
+    fn save_stats(&self) {
+        if !self.stats_unreadable {
+            let _ = write_stats(&self.stats.as_string());
+        }
+    }
+
     fn translate_click_coordinates(&self, x: usize, y: usize) -> Option<(usize, usize)> {
         let x = x
             .checked_sub(self.board_rect.x as usize + 1)
@@ -83,11 +231,22 @@ impl App {
         }
     }
 
+    /// Whether a menu is covering the board or a replay is playing on it, the board ignores clicks
+    /// while either is the case
+    const fn menu_open(&self) -> bool {
+        self.change_difficulty
+            || self.show_stats
+            || self.seed_input.is_some()
+            || self.resume_prompt.is_some()
+            || self.playback.is_some()
+    }
+
     pub fn left_click(&mut self, x: usize, y: usize) {
-        if self.change_difficulty {
+        if self.menu_open() {
             return;
         }
         if let Some((x, y)) = self.translate_click_coordinates(x, y) {
+            self.clear_hint();
             if self.last_click_pos.0 == x && self.last_click_pos.1 == y {
                 self.board.do_control_click(x, y);
             } else {
@@ -98,23 +257,103 @@ impl App {
     }
 
     pub fn right_click(&mut self, x: usize, y: usize) {
-        if self.change_difficulty {
+        if self.menu_open() {
             return;
         }
         if let Some((x, y)) = self.translate_click_coordinates(x, y) {
+            self.clear_hint();
             self.board.right_click(x, y);
         }
     }
 
     pub fn middle_click(&mut self, x: usize, y: usize) {
-        if self.change_difficulty {
+        if self.menu_open() || !self.question_marks {
             return;
         }
         if let Some((x, y)) = self.translate_click_coordinates(x, y) {
+            self.clear_hint();
             self.board.middle_click(x, y);
         }
     }
 
+    /// Takes back the last move, after which the game is practice and won't be scored. Taking
+    /// back a loss lets the game finish again, with a new replay and summary
+    pub fn undo(&mut self) {
+        if self.menu_open() {
+            return;
+        }
+        self.clear_hint();
+        if self.board.undo() {
+            self.last_click_pos = (usize::MAX, usize::MAX);
+            if !self.board.state().is_over() {
+                self.leaderboard_updated = false;
+                self.last_game = None;
+            }
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if self.menu_open() {
+            return;
+        }
+        self.clear_hint();
+        if self.board.redo() {
+            self.last_click_pos = (usize::MAX, usize::MAX);
+        }
+    }
+
+    /// Highlights a tile that is provably safe, or explains why there isn't one
+    pub fn hint(&mut self) {
+        if self.menu_open() || self.board.state().is_over() {
+            return;
+        }
+        self.message = if self.board.state() == GameState::NotStarted {
+            Some(String::from(match self.board.first_click_protection() {
+                FirstClickPolicy::Unprotected => "The first click isn't protected, time to guess",
+                FirstClickPolicy::SafeTile => "The first click is always safe",
+                FirstClickPolicy::Opening => "The first click always opens up an area",
+            }))
+        } else if self.board.show_hint() {
+            None
+        } else {
+            Some(String::from("No tile can be proven safe, time to guess"))
+        };
+    }
+
+    pub const fn heatmap(&self) -> bool {
+        self.heatmap
+    }
+
+    pub fn toggle_heatmap(&mut self) {
+        self.heatmap = !self.heatmap;
+        if self.heatmap && !self.board.state().is_over() {
+            self.heatmap_used = true;
+        }
+        self.update_heatmap();
+    }
+
+    /// Reanalyses the board for the heatmap if anything visible changed since the last analysis
+    fn update_heatmap(&mut self) {
+        if !self.heatmap || self.board.state().is_over() {
+            self.heatmap_analysis = None;
+            return;
+        }
+        let view = View::from_board(&self.board);
+        if self
+            .heatmap_analysis
+            .as_ref()
+            .is_none_or(|(analysed, _)| *analysed != view)
+        {
+            let analysis = analyse(&view);
+            self.heatmap_analysis = Some((view, analysis));
+        }
+    }
+
+    fn clear_hint(&mut self) {
+        self.message = None;
+        self.board.clear_hint();
+    }
+
     pub const fn difficulty(&self) -> Difficulty {
         self.board.difficulty
     }
@@ -124,52 +363,458 @@ impl App {
         self.new_game();
     }
 
-    pub fn get_board_widget(&self) -> Paragraph {
-        let time = self.board.first_move_time().map_or_else(
-            || String::from(" - 0s"),
-            |start| {
-                self.board.last_move_time().map_or_else(
-                    || format!(" - {:?}s", start.elapsed().as_secs()),
-                    |end| format!(" - {:?}s", end.duration_since(start).as_secs()),
-                )
-            },
-        );
+    pub const fn custom_field(&self) -> CustomField {
+        self.custom_field
+    }
+
+    pub fn next_difficulty(&mut self) {
+        let custom = self.custom_difficulty.clamp_to(self.term_size);
+        match self.difficulty() {
+            Difficulty::Easy => self.set_difficulty(Difficulty::Medium),
+            Difficulty::Medium => self.set_difficulty(Difficulty::Hard),
+            Difficulty::Hard => self.set_difficulty(custom),
+            Difficulty::Custom { .. } => self.set_difficulty(Difficulty::Easy),
+        }
+    }
+
+    pub fn previous_difficulty(&mut self) {
+        let custom = self.custom_difficulty.clamp_to(self.term_size);
+        match self.difficulty() {
+            Difficulty::Easy => self.set_difficulty(custom),
+            Difficulty::Medium => self.set_difficulty(Difficulty::Easy),
+            Difficulty::Hard => self.set_difficulty(Difficulty::Medium),
+            Difficulty::Custom { .. } => self.set_difficulty(Difficulty::Hard),
+        }
+    }
+
+    pub const fn next_custom_field(&mut self) {
+        self.custom_field = match self.custom_field {
+            CustomField::Width => CustomField::Height,
+            CustomField::Height => CustomField::Mines,
+            CustomField::Mines => CustomField::Width,
+        };
+    }
+
+    /// Changes the selected field of the custom difficulty, only does anything while a custom
+    /// difficulty is selected
+    pub fn adjust_custom(&mut self, delta: isize) {
+        if let Difficulty::Custom {
+            mut width,
+            mut height,
+            mut mines,
+        } = self.difficulty()
+        {
+            match self.custom_field {
+                CustomField::Width => width = width.saturating_add_signed(delta),
+                CustomField::Height => height = height.saturating_add_signed(delta),
+                CustomField::Mines => mines = mines.saturating_add_signed(delta),
+            }
+            let difficulty = Difficulty::Custom {
+                width,
+                height,
+                mines,
+            }
+            .clamp_to(self.term_size);
+            self.custom_difficulty = difficulty;
+            if difficulty != self.difficulty() {
+                self.set_difficulty(difficulty);
+            }
+        }
+    }
+
+    pub fn get_board_widget(&self) -> Paragraph<'_> {
+        let elapsed = self
+            .playback
+            .as_ref()
+            .map_or_else(|| self.board.state().elapsed(), Playback::clock);
+        let time = format!(" - {:?}s", elapsed.as_secs());
         let title = Line::default().spans(vec![
             Span::raw("Minesweeper: ").bold(),
-            self.difficulty().as_span(),
+            self.difficulty().as_span(&self.theme),
             Span::raw(time),
+            Span::raw(format!(" - #{}", self.board.seed())).fg(self.theme.muted),
         ]);
-        self.board.to_widget().block(
-            Block::new()
-                .borders(Borders::ALL)
-                .title(title)
-                .title_alignment(Alignment::Center),
-        )
+        let title = if !self.board.no_guess {
+            title
+        } else if self.board.is_guess_free() || self.board.state() == GameState::NotStarted {
+            title.spans([Span::raw(" - No guess").fg(self.theme.good)])
+        } else {
+            title.spans([Span::raw(" - No guess board not found").fg(self.theme.bad)])
+        };
+        let title = match &self.playback {
+            Some(playback) if playback.paused => {
+                title.spans([
+                    Span::raw(format!(" - Replay {}x (paused)", playback.speed()))
+                        .fg(self.theme.info),
+                ])
+            }
+            Some(playback) => title
+                .spans([Span::raw(format!(" - Replay {}x", playback.speed())).fg(self.theme.info)]),
+            None => title,
+        };
+        let title = if self.board.is_practice() {
+            title.spans([Span::raw(" - Practice").fg(self.theme.info)])
+        } else {
+            title
+        };
+        let mut block = Block::new()
+            .borders(Borders::ALL)
+            .border_set(self.glyphs.border)
+            .title(title)
+            .title_alignment(Alignment::Center);
+        if let Some(message) = &self.message {
+            block = block.title_bottom(
+                Line::from(message.as_str())
+                    .fg(self.theme.message)
+                    .centered(),
+            );
+        }
+        let heatmap = self.heatmap_analysis.as_ref().map(|(_, analysis)| analysis);
+        self.board
+            .to_widget(heatmap, &self.theme, &self.glyphs)
+            .block(block)
     }
 
-    pub fn get_leaderboard_widget(&self) -> List {
-        let items = self
+    pub fn get_leaderboard_widget(&self) -> List<'_> {
+        // Once a hint has been used the game can only place on the hinted leaderboard, so show that
+        let hinted = self.board.hints_used() > 0;
+        let mut scores: Vec<&Score> = self
             .leaderboard
             .iter()
-            .filter(|score| score.difficulty() == self.difficulty())
-            .map(|score| score.as_list_item());
+            .filter(|score| score.difficulty == self.difficulty())
+            .filter(|score| (score.hints > 0) == hinted)
+            .collect();
+        scores.sort_by(|a, b| self.leaderboard_sort.compare(a, b));
+        let items = scores
+            .into_iter()
+            .map(|score| score.as_list_item(self.leaderboard_sort, &self.theme));
+        let mut title = String::from("Leaderboard");
+        if hinted {
+            title += " (hints)";
+        }
+        if self.leaderboard_sort != ScoreSort::Time {
+            title += " by ";
+            title += self.leaderboard_sort.as_static_str();
+        }
         List::new(items).block(
             Block::default()
                 .borders(Borders::NONE)
-                .title("Leaderboard")
+                .title(title)
                 .title_alignment(Alignment::Center),
         )
     }
 
-    pub fn get_board_size_with_border(&self) -> (u16, u16) {
+    pub const fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    pub const fn next_leaderboard_sort(&mut self) {
+        self.leaderboard_sort = self.leaderboard_sort.next();
+    }
+
+    /// Metrics of the last finished game, shown until the next game starts
+    pub fn get_summary_widget(&self) -> Option<Paragraph<'_>> {
+        let (metrics, time) = self.last_game?;
+        let lines = vec![
+            Line::from(format!("Time: {}s", format_seconds(time))),
+            Line::from(format!("3BV: {}", metrics.bbbv)),
+            Line::from(format!(
+                "Clicks: {} ({} effective)",
+                metrics.clicks, metrics.effective_clicks
+            )),
+            Line::from(format!("Efficiency: {:.0}%", metrics.efficiency() * 100.)),
+            Line::from(format!("3BV/s: {:.2}", metrics.bbbv_per_second(time))),
+            Line::from(format!("RQP: {:.1}", metrics.rqp(time))),
+        ];
+        Some(
+            Paragraph::new(lines).centered().block(
+                Block::default()
+                    .borders(Borders::BOTTOM)
+                    .border_set(self.glyphs.border)
+                    .title("Last game")
+                    .title_alignment(Alignment::Center),
+            ),
+        )
+    }
+
+    pub const fn get_board_size_with_border(&self) -> (u16, u16) {
         let (x, y) = self.board.get_board_size();
         #[allow(clippy::cast_possible_truncation)]
         (x as u16 * 3 + 2, y as u16 * 3 + 2)
     }
 
+    fn start_game(&mut self, board: Board) {
+        // A game that was started but never finished is abandoned
+        if self.playback.is_none() && self.game_counted && !self.result_counted {
+            self.stats.abandon(self.board.difficulty);
+            self.save_stats();
+        }
+        self.game_counted = false;
+        self.result_counted = false;
+        self.leaderboard_updated = false;
+        self.last_game = None;
+        self.message = None;
+        self.heatmap_used = self.heatmap;
+        self.board = board;
+        // Imported and replayed boards come with their mines, there is nothing left to generate
+        self.board.no_guess = self.no_guess && !self.board.layout_settled();
+        self.board.first_click = self.first_click;
+    }
+
     pub fn new_game(&mut self) {
+        self.start_game(Board::new(self.board.difficulty, self.term_size));
+    }
+
+    pub fn new_game_with_seed(&mut self, seed: u64) {
+        self.start_game(Board::with_seed(
+            self.board.difficulty,
+            self.term_size,
+            seed,
+        ));
+    }
+
+    pub const fn keys(&self) -> &KeyBindings {
+        &self.keys
+    }
+
+    pub const fn question_marks(&self) -> bool {
+        self.question_marks
+    }
+
+    pub const fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    pub const fn glyphs(&self) -> &Glyphs {
+        &self.glyphs
+    }
+
+    pub fn next_theme(&mut self) {
+        self.theme = self.theme.next();
+    }
+
+    /// Shows a message under the board until the next game starts
+    pub fn show_message(&mut self, message: String) {
+        self.message = Some(message);
+    }
+
+    pub const fn first_click(&self) -> FirstClickPolicy {
+        self.first_click
+    }
+
+    /// Switches to the next first click policy for new games, a game that hasn't started yet is
+    /// switched over straight away
+    pub const fn next_first_click(&mut self) {
+        self.first_click = self.first_click.next();
+        if matches!(self.board.state(), GameState::NotStarted) {
+            self.board.first_click = self.first_click;
+        }
+    }
+
+    /// Toggles no guess generation for new games, a game that hasn't started yet is switched over
+    /// straight away
+    pub const fn toggle_no_guess(&mut self) {
+        self.no_guess = !self.no_guess;
+        if matches!(self.board.state(), GameState::NotStarted) && !self.board.layout_settled() {
+            self.board.no_guess = self.no_guess;
+        }
+    }
+
+    /// Starts a game from the seed typed into the seed prompt and closes it, leaves the prompt
+    /// open if the input isn't a valid seed
+    pub fn submit_seed(&mut self) {
+        if let Some(seed) = self
+            .seed_input
+            .as_ref()
+            .and_then(|input| input.parse().ok())
+        {
+            self.new_game_with_seed(seed);
+            self.seed_input = None;
+        }
+    }
+
+    /// Starts a game on the layout in the board file, or explains what is wrong with the file
+    pub fn import_board(&mut self) {
+        if self.menu_open() {
+            return;
+        }
+        let path = board_path().map_or_else(String::new, |path| path.display().to_string());
+        let board = match read_board() {
+            Ok(Some(layout)) => parse_layout(layout.lines(), 1)
+                .and_then(|mines| Board::import(&mines, self.term_size)),
+            Ok(None) => Err(anyhow::anyhow!("No board to import, put one in {path}")),
+            Err(err) => Err(err),
+        };
+        match board {
+            Ok(board) => {
+                self.start_game(board);
+                self.message = Some(format!("Imported {path}"));
+            }
+            Err(err) => self.message = Some(format!("Couldn't import the board: {err}")),
+        }
+    }
+
+    /// Writes the current mine layout to the board file
+    pub fn export_board(&mut self) {
+        if self.menu_open() {
+            return;
+        }
+        let mut layout = layout_to_lines(&self.board.mine_layout()).join("\n");
+        layout.push('\n');
+        self.message = Some(match write_board(&layout) {
+            Ok(()) => format!(
+                "Exported the board to {}",
+                board_path().unwrap_or_default().display()
+            ),
+            Err(err) => format!("Couldn't export the board: {err}"),
+        });
+    }
+
+    /// Starts watching the last finished game again
+    pub fn watch_replay(&mut self) {
+        if self.menu_open() {
+            return;
+        }
+        let replay = match read_replay() {
+            Ok(Some(replay)) => Replay::parse(&replay),
+            Ok(None) => {
+                self.message = Some(String::from("There is no replay to watch"));
+                return;
+            }
+            Err(err) => Err(err),
+        };
+        match replay {
+            Ok(replay) if check_compatible(replay.mines.size(), self.term_size) => {
+                self.start_playback(replay);
+            }
+            Ok(_) => self.message = Some(String::from("The replay doesn't fit in the terminal")),
+            Err(err) => self.message = Some(format!("Couldn't read the replay: {err}")),
+        }
+    }
+
+    /// Starts watching the game in the RAW video file, or explains what is wrong with the file
+    pub fn watch_rawvf(&mut self) {
+        if self.menu_open() {
+            return;
+        }
+        let path = rawvf_path().map_or_else(String::new, |path| path.display().to_string());
+        let replay = match read_rawvf() {
+            Ok(Some(rawvf)) => parse_rawvf(&rawvf),
+            Ok(None) => Err(anyhow::anyhow!("No game to import, put one in {path}")),
+            Err(err) => Err(err),
+        };
+        match replay {
+            Ok(replay) if check_compatible(replay.mines.size(), self.term_size) => {
+                self.start_playback(replay);
+            }
+            Ok(_) => self.message = Some(String::from("The game doesn't fit in the terminal")),
+            Err(err) => self.message = Some(format!("Couldn't import the game: {err}")),
+        }
+    }
+
+    /// Writes the last finished game to the RAW video file
+    pub fn export_rawvf(&mut self) {
+        if self.menu_open() {
+            return;
+        }
+        let rawvf = match read_replay() {
+            Ok(Some(replay)) => Replay::parse(&replay).and_then(|replay| to_rawvf(&replay)),
+            Ok(None) => Err(anyhow::anyhow!("there is no finished game")),
+            Err(err) => Err(err),
+        };
+        self.message = Some(match rawvf.and_then(|rawvf| write_rawvf(&rawvf)) {
+            Ok(()) => format!(
+                "Exported the last game to {}",
+                rawvf_path().unwrap_or_default().display()
+            ),
+            Err(err) => format!("Couldn't export the last game: {err}"),
+        });
+    }
+
+    fn start_playback(&mut self, replay: Replay) {
+        let (playback, board) = Playback::new(replay, self.term_size);
+        self.start_game(board);
+        self.playback = Some(playback);
+    }
+
+    pub const fn playback_active(&self) -> bool {
+        self.playback.is_some()
+    }
+
+    /// Stops watching a replay and goes back to a fresh game
+    pub fn stop_playback(&mut self) {
+        if self.playback.take().is_some() {
+            self.new_game();
+        }
+    }
+
+    pub const fn toggle_playback_pause(&mut self) {
+        if let Some(playback) = self.playback.as_mut() {
+            playback.paused = !playback.paused;
+        }
+    }
+
+    pub fn step_playback(&mut self) {
+        if let Some(playback) = self.playback.as_mut() {
+            playback.step(&mut self.board);
+        }
+    }
+
+    pub const fn set_playback_speed(&mut self, speed: u32) {
+        if let Some(playback) = self.playback.as_mut() {
+            playback.set_speed(speed);
+        }
+    }
+
+    /// Offers to resume a saved game, a save that can't be read is thrown away
+    pub fn offer_resume(&mut self, save: &str) {
+        match SavedGame::parse(save, self.term_size) {
+            Ok(saved) => self.resume_prompt = Some(saved),
+            Err(err) => {
+                self.message = Some(format!(
+                    "The saved game couldn't be read and was discarded: {err}"
+                ));
+                let _ = remove_save();
+            }
+        }
+    }
+
+    pub const fn resume_prompt_open(&self) -> bool {
+        self.resume_prompt.is_some()
+    }
+
+    /// Answers the resume prompt, the save is only ever resumed once
+    pub fn answer_resume_prompt(&mut self, resume: bool) {
+        let Some(saved) = self.resume_prompt.take() else {
+            return;
+        };
+        let _ = remove_save();
+        if !resume {
+            // Turning the game down abandons it, it was counted as started when it was played
+            self.stats.abandon(saved.board.difficulty);
+            self.save_stats();
+            return;
+        }
+        self.heatmap_used = saved.heatmap_used || self.heatmap;
+        self.no_guess = saved.board.no_guess;
+        self.first_click = saved.board.first_click;
+        self.board = saved.resume();
+        // The game was counted as started in the session it was saved from
+        self.game_counted = true;
+        self.result_counted = false;
         self.leaderboard_updated = false;
-        self.board = Board::new(self.board.difficulty, self.term_size);
+        self.message = None;
+        self.last_click_pos = (usize::MAX, usize::MAX);
+    }
+
+    /// Saves the game being played so the next session can pick it up, or clears out an old save
+    /// if there is nothing to keep. A save that was never answered is left alone
+    pub fn save_game(&self) -> anyhow::Result<()> {
+        if self.resume_prompt.is_some() || self.playback.is_some() {
+            return Ok(());
+        }
+        SavedGame::serialise(&self.board, self.heatmap_used)
+            .map_or_else(remove_save, |save| write_save(&save))
     }
 
     pub fn update_size(&mut self, term_size: (u16, u16)) {
@@ -177,3 +822,72 @@ impl App {
         self.board.set_max_board_size(term_size);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{grid::Grid, io::set_data_dir};
+
+    /// An app that keeps its files in a temporary folder shared by the tests here
+    fn test_app() -> (App, std::path::PathBuf) {
+        let dir = std::env::temp_dir().join(format!("termsweeper-app-{}", std::process::id()));
+        set_data_dir(dir.clone());
+        (App::new(&Config::default(), None, (200, 100)), dir)
+    }
+
+    #[test]
+    fn undoing_a_loss_lets_the_game_finish_again() {
+        let (mut app, dir) = test_app();
+        let mut mines = Grid::new(4, 4, false);
+        mines[(3, 0)] = true;
+        mines[(3, 3)] = true;
+        let difficulty = Difficulty::Custom {
+            width: 4,
+            height: 4,
+            mines: 2,
+        };
+        app.start_game(Board::from_mines(difficulty, 0, &mines, (200, 100)));
+
+        app.board.left_click(0, 0);
+        app.board.left_click(3, 0);
+        app.tick();
+        assert!(app.last_game.is_some());
+
+        app.undo();
+        assert!(matches!(app.board.state(), GameState::Playing { .. }));
+        assert!(app.last_game.is_none());
+        app.tick();
+        app.board.left_click(3, 1);
+        app.board.left_click(3, 2);
+        app.tick();
+        assert!(matches!(app.board.state(), GameState::Won { .. }));
+        assert!(app.last_game.is_some());
+
+        // The loss is what the stats keep
+        let (_, stats) = app
+            .stats
+            .iter()
+            .find(|(&played, _)| played == difficulty)
+            .unwrap();
+        assert_eq!((stats.started, stats.won, stats.lost), (1, 0, 1));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn boards_with_their_own_mines_are_never_no_guess() {
+        let (mut app, _) = test_app();
+        app.toggle_no_guess();
+        assert!(app.board.no_guess);
+
+        let mut mines = Grid::new(4, 4, false);
+        mines[(3, 3)] = true;
+        app.start_game(Board::import(&mines, (200, 100)).unwrap());
+        assert!(!app.board.no_guess);
+        app.toggle_no_guess();
+        app.toggle_no_guess();
+        assert!(!app.board.no_guess);
+
+        app.new_game();
+        assert!(app.board.no_guess);
+    }
+}
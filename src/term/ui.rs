@@ -1,13 +1,116 @@
 use ratatui::{
-    prelude::{Alignment, Constraint::*, Direction, Layout},
+    prelude::{Alignment, Constraint::*, Direction, Layout, Rect},
     style::{Style, Stylize},
-    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap},
+    text::{Line, Span},
+    widgets::{
+        Block, Borders, Cell, Clear, List, ListItem, ListState, Paragraph, Row, Table, Wrap,
+    },
     Frame,
 };
 
-use crate::types::Difficulty;
+use crate::{
+    config::KeyAction,
+    types::{heatmap_legend, Difficulty},
+};
+
+use super::app::{App, CustomField};
+
+fn custom_difficulty_item(app: &App) -> ListItem<'static> {
+    let Difficulty::Custom {
+        width,
+        height,
+        mines,
+    } = app.difficulty()
+    else {
+        return ListItem::from(Span::raw("Custom").italic().fg(app.theme().custom));
+    };
+    let field = |value: usize, field: CustomField| {
+        let span = Span::raw(value.to_string());
+        if app.custom_field() == field {
+            span.underlined().bold()
+        } else {
+            span
+        }
+    };
+    ListItem::from(Line::from(vec![
+        Span::raw("Custom ").italic().fg(app.theme().custom),
+        field(width, CustomField::Width),
+        Span::raw("x"),
+        field(height, CustomField::Height),
+        Span::raw(", "),
+        field(mines, CustomField::Mines),
+        Span::raw(" mines"),
+    ]))
+}
+
+/// The controls shown to the left of the board, with whatever keys are bound
+fn instructions(app: &App) -> Paragraph<'static> {
+    let key = |action| app.keys().key(action);
+    let question_marks = if app.question_marks() {
+        "\nMiddle click to mark a tile as '?'\n"
+    } else {
+        ""
+    };
+    Paragraph::new(format!(
+        r"
+Left click to uncover tiles
+
+Double click to uncover tiles near marked mines
+{question_marks}
+Right click to flag a tile as a mine
+
+Press {} to restart
+
+Press {} to change the difficulty
+
+Press {} to play a seed
+
+Press {} to toggle boards that never need a guess
+
+Press {} for a hint, hinted games have their own leaderboard
+
+Press {} to show mine probabilities, games played with them aren't scored
+
+Press {} to undo and {} to redo, games with undone moves aren't scored
 
-use super::app::App;
+Press {} to watch the last game again, Space pauses, Right steps and 1, 2 or 4 set the speed
+
+Press {} to export the mine layout and {} to import one, imported games aren't scored
+
+Press {} to export the last game as a RAW video and {} to watch one
+
+Press {} to sort the leaderboard by time, 3BV, efficiency, 3BV/s or RQP
+
+Press {} to see your stats
+
+Use Tab and Left/Right to edit a custom difficulty
+
+Press {} to change the first click protection: {}
+
+Press {} to change the colour theme: {}",
+        key(KeyAction::NewGame),
+        key(KeyAction::Difficulty),
+        key(KeyAction::Seed),
+        key(KeyAction::NoGuess),
+        key(KeyAction::Hint),
+        key(KeyAction::Heatmap),
+        key(KeyAction::Undo),
+        key(KeyAction::Redo),
+        key(KeyAction::Replay),
+        key(KeyAction::ExportBoard),
+        key(KeyAction::ImportBoard),
+        key(KeyAction::ExportRawvf),
+        key(KeyAction::WatchRawvf),
+        key(KeyAction::Sort),
+        key(KeyAction::Stats),
+        key(KeyAction::FirstClick),
+        app.first_click().as_static_str(),
+        key(KeyAction::Theme),
+        app.theme().name
+    ))
+    .centered()
+    .wrap(Wrap { trim: true })
+}
 
 pub fn render(app: &mut App, f: &mut Frame) {
     let (min_x, min_y) = app.get_board_size_with_border();
@@ -37,72 +140,206 @@ pub fn render(app: &mut App, f: &mut Frame) {
     f.render_widget(app.get_board_widget().centered(), app.board_rect);
 
     // Render game controls
-    let instructions = Paragraph::new(
-        r"
-Left click to uncover tiles
+    f.render_widget(instructions(app), horizontal[0]);
 
-Double click to uncover tiles near marked mines
+    // Render leaderboard
+    render_side_panel(app, f, horizontal[2]);
 
-Middle click to mark a tile as '?'
+    // Render difficulty change ui if requested
+    if app.change_difficulty {
+        render_difficulty_menu(app, f);
+    }
 
-Right click to flag a tile as a mine
+    // Render stats screen if requested
+    if app.show_stats {
+        render_stats(app, f);
+    }
 
-Press R to restart
+    // Render seed prompt if requested
+    if let Some(input) = &app.seed_input {
+        let area = centered_rect(30, 3, f.size());
+        let prompt = Paragraph::new(Line::from(vec![
+            Span::raw(input.as_str()),
+            Span::raw("_").slow_blink(),
+        ]))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_set(app.glyphs().border)
+                .title("Seed")
+                .title_alignment(Alignment::Center),
+        );
+        f.render_widget(Clear, area);
+        f.render_widget(prompt, area);
+    }
 
-Press C to change the difficulty",
-    )
-    .centered()
-    .wrap(Wrap { trim: true });
-    f.render_widget(instructions, horizontal[0]);
+    // Render resume prompt if there is a saved game
+    if app.resume_prompt_open() {
+        let area = centered_rect(36, 4, f.size());
+        let prompt = Paragraph::new(vec![
+            Line::from("Resume the unfinished game?"),
+            Line::from("Y to resume, N to start over").fg(app.theme().muted),
+        ])
+        .centered()
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_set(app.glyphs().border)
+                .title("Saved game")
+                .title_alignment(Alignment::Center),
+        );
+        f.render_widget(Clear, area);
+        f.render_widget(prompt, area);
+    }
+}
 
-    // Render leaderboard
-    f.render_widget(app.get_leaderboard_widget(), horizontal[2]);
+/// Renders the leaderboard, with the summary of the last game above it and the heatmap legend
+/// under it while the heatmap is shown
+fn render_side_panel(app: &App, f: &mut Frame, area: Rect) {
+    let area = app.get_summary_widget().map_or(area, |summary| {
+        // Title, six metrics and the bottom border
+        let side = Layout::new(Direction::Vertical, [Length(8), Min(0)]).split(area);
+        f.render_widget(summary, side[0]);
+        side[1]
+    });
+    if app.heatmap() {
+        let legend = heatmap_legend(app.theme(), app.glyphs());
+        #[allow(clippy::cast_possible_truncation)]
+        let legend_height = legend.len() as u16 + 2;
+        let side = Layout::new(Direction::Vertical, [Min(0), Length(legend_height)]).split(area);
+        f.render_widget(app.get_leaderboard_widget(), side[0]);
+        let legend = Paragraph::new(legend).centered().block(
+            Block::default()
+                .borders(Borders::TOP)
+                .border_set(app.glyphs().border)
+                .title("Mine probability")
+                .title_alignment(Alignment::Center),
+        );
+        f.render_widget(legend, side[1]);
+    } else {
+        f.render_widget(app.get_leaderboard_widget(), area);
+    }
+}
 
-    // Render difficulty change ui if requested
-    if app.change_difficulty {
-        let size_x = 30;
-        let size_y = 8;
-        let spare_x = f.size().width.saturating_sub(size_x);
-        let spare_y = f.size().height.saturating_sub(size_y);
-        let vertical = Layout::new(
-            Direction::Vertical,
-            [
-                Length(spare_y.saturating_div(2)),
-                Min(size_y),
-                Length(spare_y.saturating_div(2)),
-            ],
-        )
-        .split(f.size());
-        let horizontal = Layout::new(
-            Direction::Horizontal,
-            [
-                Length(spare_x.saturating_div(2)),
-                Min(size_x),
-                Length(spare_x.saturating_div(2)),
-            ],
+fn render_difficulty_menu(app: &App, f: &mut Frame) {
+    let area = centered_rect(30, 9, f.size());
+    let items = vec![Difficulty::Easy, Difficulty::Medium, Difficulty::Hard]
+        .into_iter()
+        .map(|difficulty| ListItem::from(difficulty.as_span(app.theme())))
+        .chain([custom_difficulty_item(app)]);
+    let index = match app.difficulty() {
+        Difficulty::Easy => 0,
+        Difficulty::Medium => 1,
+        Difficulty::Hard => 2,
+        Difficulty::Custom { .. } => 3,
+    };
+    let list = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_set(app.glyphs().border)
+                .title("Difficulty")
+                .title_alignment(Alignment::Center),
         )
-        .split(vertical[1]);
-        let items = vec![Difficulty::Easy, Difficulty::Medium, Difficulty::Hard]
-            .into_iter()
-            .map(|difficulty| ListItem::from(difficulty.as_span()));
-        let index = match app.difficulty() {
-            Difficulty::Easy => 0,
-            Difficulty::Medium => 1,
-            Difficulty::Hard => 2,
-        };
-        let list = List::new(items)
-            .block(
-                Block::default()
-                    .borders(Borders::ALL)
-                    .title("Difficulty")
-                    .title_alignment(Alignment::Center),
+        .highlight_style(Style::new().reversed());
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(
+        list,
+        area,
+        &mut ListState::default().with_selected(Some(index)),
+    );
+}
+
+fn render_stats(app: &App, f: &mut Frame) {
+    let rows: Vec<Row> = app
+        .stats()
+        .iter()
+        .map(|(difficulty, stats)| {
+            let average = stats.average_win_time().map_or_else(
+                || String::from("-"),
+                |time| format!("{:.1}s", time.as_secs_f64()),
+            );
+            let win_rate = stats
+                .win_rate()
+                .map_or_else(|| String::from("-"), |rate| format!("{rate:.0}%"));
+            Row::new([
+                Cell::from(difficulty.as_span(app.theme())),
+                Cell::from(stats.started.to_string()),
+                Cell::from(stats.won.to_string()),
+                Cell::from(stats.lost.to_string()),
+                Cell::from(stats.abandoned.to_string()),
+                Cell::from(win_rate),
+                Cell::from(stats.streak.to_string()),
+                Cell::from(stats.best_streak.to_string()),
+                Cell::from(average),
+            ])
+        })
+        .collect();
+    #[allow(clippy::cast_possible_truncation)]
+    let height = rows.len().max(1) as u16 + 4;
+    let header = Row::new([
+        "Difficulty",
+        "Started",
+        "Won",
+        "Lost",
+        "Abandoned",
+        "Win rate",
+        "Streak",
+        "Best",
+        "Avg win",
+    ])
+    .bold();
+    let widths = [
+        Length(20),
+        Length(7),
+        Length(5),
+        Length(5),
+        Length(9),
+        Length(8),
+        Length(6),
+        Length(5),
+        Length(8),
+    ];
+    let table = Table::new(rows, widths).header(header).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_set(app.glyphs().border)
+            .title("Stats")
+            .title_bottom(
+                Line::from(format!(
+                    "Press {} to close",
+                    app.keys().key(KeyAction::Stats)
+                ))
+                .fg(app.theme().muted)
+                .centered(),
             )
-            .highlight_style(Style::new().reversed());
-        f.render_widget(Clear, horizontal[1]);
-        f.render_stateful_widget(
-            list,
-            horizontal[1],
-            &mut ListState::default().with_selected(Some(index)),
-        );
-    }
+            .title_alignment(Alignment::Center),
+    );
+    let area = centered_rect(94, height, f.size());
+    f.render_widget(Clear, area);
+    f.render_widget(table, area);
+}
+
+/// Centers an area of the requested size inside of `area`
+fn centered_rect(size_x: u16, size_y: u16, area: Rect) -> Rect {
+    let spare_x = area.width.saturating_sub(size_x);
+    let spare_y = area.height.saturating_sub(size_y);
+    let vertical = Layout::new(
+        Direction::Vertical,
+        [
+            Length(spare_y.saturating_div(2)),
+            Min(size_y),
+            Length(spare_y.saturating_div(2)),
+        ],
+    )
+    .split(area);
+    Layout::new(
+        Direction::Horizontal,
+        [
+            Length(spare_x.saturating_div(2)),
+            Min(size_x),
+            Length(spare_x.saturating_div(2)),
+        ],
+    )
+    .split(vertical[1])[1]
 }
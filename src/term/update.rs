@@ -1,48 +1,156 @@
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
 
 use super::app::App;
-use crate::types::Difficulty;
+use crate::{config::KeyAction, replay::PLAYBACK_SPEEDS};
 
-pub fn handle_keys(app: &mut App, key_event: KeyEvent) {
+fn handle_seed_keys(app: &mut App, key_event: KeyEvent) {
     match key_event.code {
-        KeyCode::Esc | KeyCode::Char('q' | 'Q') => {
-            if app.change_difficulty {
-                app.change_difficulty = false;
-            } else {
-                app.should_quit = true;
+        KeyCode::Esc => app.seed_input = None,
+        KeyCode::Enter => app.submit_seed(),
+        KeyCode::Backspace => {
+            if let Some(input) = app.seed_input.as_mut() {
+                input.pop();
             }
         }
-        KeyCode::Char('c' | 'C') => {
-            if key_event.modifiers == KeyModifiers::CONTROL {
-                app.should_quit = true;
-            } else {
-                app.change_difficulty = !app.change_difficulty;
+        KeyCode::Char(char) if char.is_ascii_digit() => {
+            if let Some(input) = app.seed_input.as_mut() {
+                input.push(char);
             }
         }
-        KeyCode::Enter => app.change_difficulty = false,
-        KeyCode::Char('r' | 'R') => app.new_game(),
-        KeyCode::Up => {
-            if app.change_difficulty {
-                match app.difficulty() {
-                    Difficulty::Easy => app.set_difficulty(Difficulty::Hard),
-                    Difficulty::Medium => app.set_difficulty(Difficulty::Easy),
-                    Difficulty::Hard => app.set_difficulty(Difficulty::Medium),
-                }
+        KeyCode::Char('c' | 'C') if key_event.modifiers == KeyModifiers::CONTROL => {
+            app.should_quit = true;
+        }
+        _ => {}
+    }
+}
+
+fn handle_resume_keys(app: &mut App, key_event: KeyEvent) {
+    match key_event.code {
+        KeyCode::Enter | KeyCode::Char('y' | 'Y') => app.answer_resume_prompt(true),
+        KeyCode::Esc | KeyCode::Char('n' | 'N') => app.answer_resume_prompt(false),
+        KeyCode::Char('c' | 'C') if key_event.modifiers == KeyModifiers::CONTROL => {
+            app.should_quit = true;
+        }
+        _ => {}
+    }
+}
+
+fn handle_playback_keys(app: &mut App, key_event: KeyEvent) {
+    match key_event.code {
+        KeyCode::Char('c' | 'C') if key_event.modifiers == KeyModifiers::CONTROL => {
+            app.should_quit = true;
+        }
+        KeyCode::Esc => app.stop_playback(),
+        KeyCode::Char(key)
+            if matches!(
+                app.keys().action(key),
+                Some(KeyAction::Quit | KeyAction::Replay)
+            ) =>
+        {
+            app.stop_playback();
+        }
+        KeyCode::Char(' ') => app.toggle_playback_pause(),
+        KeyCode::Right | KeyCode::Char('.') => app.step_playback(),
+        KeyCode::Char(char) => {
+            if let Some(speed) = char
+                .to_digit(10)
+                .filter(|speed| PLAYBACK_SPEEDS.contains(speed))
+            {
+                app.set_playback_speed(speed);
+            }
+        }
+        _ => {}
+    }
+}
+
+pub fn handle_keys(app: &mut App, key_event: KeyEvent) {
+    if app.playback_active() {
+        handle_playback_keys(app, key_event);
+        return;
+    }
+    if app.resume_prompt_open() {
+        handle_resume_keys(app, key_event);
+        return;
+    }
+    if app.show_stats {
+        match key_event.code {
+            KeyCode::Char('c' | 'C') if key_event.modifiers == KeyModifiers::CONTROL => {
+                app.should_quit = true;
+            }
+            KeyCode::Esc | KeyCode::Enter => app.show_stats = false,
+            KeyCode::Char(key)
+                if matches!(
+                    app.keys().action(key),
+                    Some(KeyAction::Quit | KeyAction::Stats)
+                ) =>
+            {
+                app.show_stats = false;
             }
+            _ => {}
         }
-        KeyCode::Down => {
-            if app.change_difficulty {
-                match app.difficulty() {
-                    Difficulty::Easy => app.set_difficulty(Difficulty::Medium),
-                    Difficulty::Medium => app.set_difficulty(Difficulty::Hard),
-                    Difficulty::Hard => app.set_difficulty(Difficulty::Easy),
-                }
+        return;
+    }
+    if app.seed_input.is_some() {
+        handle_seed_keys(app, key_event);
+        return;
+    }
+    match key_event.code {
+        KeyCode::Char('c' | 'C') if key_event.modifiers == KeyModifiers::CONTROL => {
+            app.should_quit = true;
+        }
+        KeyCode::Esc => quit_or_close_menu(app),
+        KeyCode::Enter => app.change_difficulty = false,
+        KeyCode::Char(key) => {
+            if let Some(action) = app.keys().action(key) {
+                handle_action(app, action);
             }
         }
+        KeyCode::Up if app.change_difficulty => app.previous_difficulty(),
+        KeyCode::Down if app.change_difficulty => app.next_difficulty(),
+        KeyCode::Left if app.change_difficulty => app.adjust_custom(-1),
+        KeyCode::Right if app.change_difficulty => app.adjust_custom(1),
+        KeyCode::Tab if app.change_difficulty => app.next_custom_field(),
         _ => {}
     }
 }
 
+const fn quit_or_close_menu(app: &mut App) {
+    if app.change_difficulty {
+        app.change_difficulty = false;
+    } else {
+        app.should_quit = true;
+    }
+}
+
+fn handle_action(app: &mut App, action: KeyAction) {
+    match action {
+        KeyAction::Quit => quit_or_close_menu(app),
+        KeyAction::Difficulty => app.change_difficulty = !app.change_difficulty,
+        KeyAction::NewGame => app.new_game(),
+        KeyAction::NoGuess => app.toggle_no_guess(),
+        KeyAction::FirstClick => app.next_first_click(),
+        KeyAction::Hint => app.hint(),
+        KeyAction::Heatmap => app.toggle_heatmap(),
+        KeyAction::Undo => app.undo(),
+        KeyAction::Redo => app.redo(),
+        KeyAction::Replay => app.watch_replay(),
+        KeyAction::ImportBoard => app.import_board(),
+        KeyAction::ExportBoard => app.export_board(),
+        KeyAction::WatchRawvf => app.watch_rawvf(),
+        KeyAction::ExportRawvf => app.export_rawvf(),
+        KeyAction::Sort => app.next_leaderboard_sort(),
+        KeyAction::Theme => app.next_theme(),
+        KeyAction::Stats => {
+            app.change_difficulty = false;
+            app.show_stats = true;
+        }
+        KeyAction::Seed => {
+            app.change_difficulty = false;
+            app.seed_input = Some(String::new());
+        }
+    }
+}
+
 pub fn handle_mouse(app: &mut App, mouse_event: MouseEvent) {
     let x = mouse_event.column;
     let y = mouse_event.row;
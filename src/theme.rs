@@ -0,0 +1,324 @@
+use ratatui::{
+    style::{Color, Modifier, Style},
+    symbols::border,
+};
+
+use crate::types::Difficulty;
+
+/// Colours from the Okabe-Ito palette, which stays distinct with red-green colour blindness
+const SKY_BLUE: Color = Color::Rgb(86, 180, 233);
+const BLUISH_GREEN: Color = Color::Rgb(0, 158, 115);
+const VERMILLION: Color = Color::Rgb(213, 94, 0);
+const BLUE: Color = Color::Rgb(0, 114, 178);
+const ORANGE: Color = Color::Rgb(230, 159, 0);
+const REDDISH_PURPLE: Color = Color::Rgb(204, 121, 167);
+const YELLOW: Color = Color::Rgb(240, 228, 66);
+
+const fn fg(color: Color) -> Style {
+    Style::new().fg(color)
+}
+
+/// The colours everything on the board is drawn in
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    pub name: &'static str,
+    /// Colours of the numbers 1 to 8
+    pub numbers: [Color; 8],
+    /// Edge of revealed tiles and the background behind their numbers
+    pub revealed: Color,
+    pub mine: Color,
+    pub flag: Color,
+    pub question: Color,
+    /// Background behind flags and question marks
+    pub marker_background: Color,
+    /// Background of the fire spreading from a mine that went off
+    pub fire: Color,
+    /// Background of a tile suggested by a hint
+    pub hint: Color,
+    /// Heatmap styles, from safe to certainly a mine, all different so every band can be told apart
+    pub probabilities: [Style; 6],
+    /// Easy, medium and hard
+    pub difficulties: [Color; 3],
+    /// Custom difficulties
+    pub custom: Color,
+    /// Titles that say things went well, like a no guess board being found
+    pub good: Color,
+    /// Titles that say things went wrong, like a no guess board not being found
+    pub bad: Color,
+    /// Titles about the kind of game being played, replays and practice, and player names
+    pub info: Color,
+    /// Messages under the board
+    pub message: Color,
+    /// Seeds, hint counts and help lines
+    pub muted: Color,
+    /// Times on the leaderboard
+    pub time: Color,
+}
+
+impl Theme {
+    pub const CLASSIC: Self = Self {
+        name: "classic",
+        numbers: [
+            Color::LightBlue,
+            Color::LightGreen,
+            Color::LightRed,
+            Color::LightMagenta,
+            Color::LightYellow,
+            Color::LightCyan,
+            Color::White,
+            Color::Gray,
+        ],
+        revealed: Color::DarkGray,
+        mine: Color::Red,
+        flag: Color::Red,
+        question: Color::Blue,
+        marker_background: Color::White,
+        fire: Color::Red,
+        hint: Color::Green,
+        probabilities: [
+            fg(Color::Green),
+            fg(Color::LightGreen),
+            fg(Color::Yellow),
+            fg(Color::LightRed),
+            fg(Color::Red),
+            fg(Color::Magenta),
+        ],
+        difficulties: [Color::Green, Color::Yellow, Color::Red],
+        custom: Color::Magenta,
+        good: Color::Green,
+        bad: Color::Red,
+        info: Color::Cyan,
+        message: Color::Yellow,
+        muted: Color::DarkGray,
+        time: Color::Blue,
+    };
+
+    /// Bright colours on black, for low contrast displays and low vision
+    pub const HIGH_CONTRAST: Self = Self {
+        name: "high-contrast",
+        numbers: [
+            Color::LightCyan,
+            Color::LightGreen,
+            Color::LightYellow,
+            Color::LightMagenta,
+            Color::LightRed,
+            Color::White,
+            Color::LightBlue,
+            Color::Yellow,
+        ],
+        revealed: Color::DarkGray,
+        mine: Color::LightRed,
+        flag: Color::LightYellow,
+        question: Color::LightCyan,
+        marker_background: Color::Black,
+        fire: Color::LightRed,
+        hint: Color::LightGreen,
+        probabilities: [
+            fg(Color::LightCyan),
+            fg(Color::LightGreen),
+            fg(Color::LightYellow),
+            fg(Color::Yellow),
+            fg(Color::LightRed),
+            fg(Color::LightMagenta),
+        ],
+        difficulties: [Color::LightGreen, Color::LightYellow, Color::LightRed],
+        custom: Color::LightMagenta,
+        good: Color::LightGreen,
+        bad: Color::LightRed,
+        info: Color::LightCyan,
+        message: Color::LightYellow,
+        muted: Color::Gray,
+        time: Color::LightBlue,
+    };
+
+    /// Blues and oranges that stay apart with deuteranopia and protanopia
+    pub const COLOUR_BLIND: Self = Self {
+        name: "colour-blind",
+        numbers: [
+            SKY_BLUE,
+            BLUISH_GREEN,
+            VERMILLION,
+            BLUE,
+            ORANGE,
+            REDDISH_PURPLE,
+            YELLOW,
+            Color::White,
+        ],
+        revealed: Color::DarkGray,
+        mine: VERMILLION,
+        flag: ORANGE,
+        question: BLUE,
+        marker_background: Color::White,
+        fire: VERMILLION,
+        hint: BLUE,
+        probabilities: [
+            fg(BLUE),
+            fg(SKY_BLUE),
+            fg(YELLOW),
+            fg(ORANGE),
+            fg(VERMILLION),
+            fg(REDDISH_PURPLE),
+        ],
+        difficulties: [SKY_BLUE, YELLOW, VERMILLION],
+        custom: REDDISH_PURPLE,
+        good: SKY_BLUE,
+        bad: VERMILLION,
+        info: BLUISH_GREEN,
+        message: YELLOW,
+        muted: Color::Gray,
+        time: SKY_BLUE,
+    };
+
+    /// Shades of grey only, tiles are told apart by their symbols
+    pub const MONOCHROME: Self = Self {
+        name: "monochrome",
+        numbers: [Color::White; 8],
+        revealed: Color::DarkGray,
+        mine: Color::Gray,
+        flag: Color::Black,
+        question: Color::Black,
+        marker_background: Color::White,
+        fire: Color::White,
+        hint: Color::Gray,
+        // Three shades aren't enough for six bands, the likeliest mines are drawn reversed
+        probabilities: [
+            fg(Color::White).add_modifier(Modifier::BOLD),
+            fg(Color::White),
+            fg(Color::Gray),
+            fg(Color::DarkGray),
+            fg(Color::Gray).add_modifier(Modifier::REVERSED),
+            fg(Color::White)
+                .add_modifier(Modifier::REVERSED)
+                .add_modifier(Modifier::BOLD),
+        ],
+        difficulties: [Color::Reset; 3],
+        custom: Color::Reset,
+        good: Color::White,
+        bad: Color::Gray,
+        info: Color::Gray,
+        message: Color::White,
+        muted: Color::DarkGray,
+        time: Color::White,
+    };
+
+    pub const ALL: [Self; 4] = [
+        Self::CLASSIC,
+        Self::HIGH_CONTRAST,
+        Self::COLOUR_BLIND,
+        Self::MONOCHROME,
+    ];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|theme| theme.name == name)
+    }
+
+    pub fn next(self) -> Self {
+        let index = Self::ALL
+            .iter()
+            .position(|theme| *theme == self)
+            .unwrap_or_default();
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    pub const fn difficulty(&self, difficulty: Difficulty) -> Color {
+        match difficulty {
+            Difficulty::Easy => self.difficulties[0],
+            Difficulty::Medium => self.difficulties[1],
+            Difficulty::Hard => self.difficulties[2],
+            Difficulty::Custom { .. } => self.custom,
+        }
+    }
+
+    /// Colour of the number of mines next to a tile, from 1 to 8
+    pub const fn number(&self, number: usize) -> Color {
+        self.numbers[number - 1]
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::CLASSIC
+    }
+}
+
+/// The characters tiles and borders are drawn with
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Glyphs {
+    /// Top and bottom edges of a tile
+    pub top: &'static str,
+    pub bottom: &'static str,
+    /// Sides of a tile around its middle character
+    pub left: &'static str,
+    pub right: &'static str,
+    /// Middle line of a hidden tile, sides included
+    pub hidden: &'static str,
+    pub flag: &'static str,
+    pub question: &'static str,
+    pub mine: &'static str,
+    pub border: border::Set,
+}
+
+impl Glyphs {
+    pub const UNICODE: Self = Self {
+        top: "▗▄▖",
+        bottom: "▝▀▘",
+        left: "▐",
+        right: "▌",
+        hidden: "▐█▌",
+        flag: "⚑",
+        question: "?",
+        mine: "*",
+        border: border::PLAIN,
+    };
+
+    /// Only 7-bit ASCII, for consoles and fonts without the block characters
+    pub const ASCII: Self = Self {
+        top: ".-.",
+        bottom: "'-'",
+        left: "|",
+        right: "|",
+        hidden: "|#|",
+        flag: "F",
+        question: "?",
+        mine: "*",
+        border: border::Set {
+            top_left: "+",
+            top_right: "+",
+            bottom_left: "+",
+            bottom_right: "+",
+            vertical_left: "|",
+            vertical_right: "|",
+            horizontal_top: "-",
+            horizontal_bottom: "-",
+        },
+    };
+
+    pub const fn new(ascii: bool) -> Self {
+        if ascii {
+            Self::ASCII
+        } else {
+            Self::UNICODE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heatmap_bands_can_be_told_apart() {
+        for theme in Theme::ALL {
+            for (index, style) in theme.probabilities.iter().enumerate() {
+                assert!(
+                    !theme.probabilities[index + 1..].contains(style),
+                    "{} has the same heatmap style twice",
+                    theme.name
+                );
+                // Black disappears on dark terminals
+                assert!(style.fg != Some(Color::Black), "{}", theme.name);
+            }
+            assert!(theme.revealed != Color::Black, "{}", theme.name);
+        }
+    }
+}
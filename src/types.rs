@@ -1,11 +1,25 @@
 use core::panic;
-use std::time::{Duration, Instant};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail, Result};
 
 use rand::prelude::*;
+
+use crate::{
+    grid::Grid,
+    history::{History, Restore},
+    metrics::{self, Metrics},
+    replay::{Action, Input},
+    solver::{self, Analysis, View},
+    theme::{Glyphs, Theme},
+};
 use ratatui::{
-    style::Stylize,
+    style::{Style, Stylize},
     text::{Line, Span},
-    widgets::{ListItem, Paragraph},
+    widgets::Paragraph,
 };
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
@@ -13,23 +27,124 @@ pub enum Difficulty {
     Easy,
     Medium,
     Hard,
+    Custom {
+        width: usize,
+        height: usize,
+        mines: usize,
+    },
 }
 
+/// Smallest width or height allowed for a custom board
+pub const MIN_CUSTOM_SIZE: usize = 4;
+
 impl Difficulty {
     pub const fn as_static_str(self) -> &'static str {
         match self {
             Self::Easy => "Easy",
             Self::Medium => "Medium",
             Self::Hard => "Hard",
+            Self::Custom { .. } => "Custom",
         }
     }
 
-    pub fn as_span(self) -> Span<'static> {
-        let span = Span::raw(self.as_static_str()).italic();
+    /// Name used to key the leaderboard, custom boards are bucketed by their size and mine count
+    pub fn as_string(self) -> String {
+        match self {
+            Self::Custom {
+                width,
+                height,
+                mines,
+            } => format!("Custom {width}x{height}/{mines}"),
+            _ => self.as_static_str().to_string(),
+        }
+    }
+
+    pub fn parse(str: &str) -> Option<Self> {
+        match str {
+            "Easy" => Some(Self::Easy),
+            "Medium" => Some(Self::Medium),
+            "Hard" => Some(Self::Hard),
+            _ => {
+                let (width, rest) = str.strip_prefix("Custom ")?.split_once('x')?;
+                let (height, mines) = rest.split_once('/')?;
+                Some(Self::Custom {
+                    width: width.parse().ok()?,
+                    height: height.parse().ok()?,
+                    mines: mines.parse().ok()?,
+                })
+            }
+        }
+    }
+
+    pub fn as_span(self, theme: &Theme) -> Span<'static> {
+        Span::raw(self.as_string())
+            .italic()
+            .fg(theme.difficulty(self))
+    }
+
+    /// Whether a board of this size can be played on this difficulty, preset difficulties come in
+    /// a few sizes to fit different terminals
+    pub fn allows_size(self, size: (usize, usize)) -> bool {
+        let sizes: &[(usize, usize)] = match self {
+            Self::Easy => &EASY_SIZES,
+            Self::Medium => &MEDIUM_SIZES,
+            Self::Hard => &HARD_SIZES,
+            Self::Custom { width, height, .. } => return size == (width, height),
+        };
+        sizes
+            .iter()
+            .any(|&(width, height)| size == (width, height) || size == (height, width))
+    }
+
+    pub const fn mines(self) -> usize {
         match self {
-            Self::Easy => span.green(),
-            Self::Medium => span.yellow(),
-            Self::Hard => span.red(),
+            Self::Easy => 10,   // 80
+            Self::Medium => 40, // 252
+            Self::Hard => 99,   // 480
+            Self::Custom { mines, .. } => mines,
+        }
+    }
+
+    /// Most mines a board can hold while still leaving room for a safe first click, boards too
+    /// dense for an opening fall back to only keeping the clicked tile clear. Sizes over
+    /// `MAX_TILES` are an error
+    pub fn max_mines(width: usize, height: usize) -> Result<usize> {
+        Ok(tile_count(width, height)?.saturating_sub(1))
+    }
+
+    /// Shrinks a custom difficulty until it fits in the terminal and has a sensible mine count,
+    /// the preset difficulties are returned unchanged
+    pub fn clamp_to(self, max_render_size: (u16, u16)) -> Self {
+        if let Self::Custom {
+            mut width,
+            mut height,
+            mines,
+        } = self
+        {
+            // Sizes from the config aren't checked, so they're cut down to something a board could
+            // be before shrinking them to the terminal
+            let longest = MAX_TILES / MIN_CUSTOM_SIZE;
+            width = width.clamp(MIN_CUSTOM_SIZE, longest);
+            height = height.clamp(MIN_CUSTOM_SIZE, longest);
+            while width > MIN_CUSTOM_SIZE
+                && !check_compatible((width, MIN_CUSTOM_SIZE), max_render_size)
+            {
+                width -= 1;
+            }
+            while height > MIN_CUSTOM_SIZE
+                && (!check_compatible((width, height), max_render_size)
+                    || tile_count(width, height).is_err())
+            {
+                height -= 1;
+            }
+            let mines = mines.clamp(1, Self::max_mines(width, height).unwrap_or(1));
+            Self::Custom {
+                width,
+                height,
+                mines,
+            }
+        } else {
+            self
         }
     }
 }
@@ -48,6 +163,7 @@ pub struct Tile {
     state: TileState,
     bombs_near: usize,
     fire: bool,
+    hint: bool,
 }
 
 impl Tile {
@@ -59,7 +175,7 @@ impl Tile {
         self.state
     }
 
-    pub fn set_state(&mut self, state: TileState) {
+    pub const fn set_state(&mut self, state: TileState) {
         self.state = state;
     }
 
@@ -67,98 +183,156 @@ impl Tile {
         self.bombs_near
     }
 
-    pub fn as_span(&self, sub_line: usize) -> Vec<Span> {
+    /// The top or bottom edge of the tile
+    fn edge_span(&self, edge: &'static str, theme: &Theme) -> Span<'static> {
+        let span = Span::raw(edge);
+        if self.tile_state() != TileState::Visible {
+            span
+        } else if self.is_mine() {
+            span.fg(theme.mine)
+        } else if self.bombs_near() == 0 {
+            Span::raw("   ")
+        } else {
+            span.fg(theme.revealed)
+        }
+    }
+
+    /// Renders one of the three lines of the tile, hidden tiles are shaded by their chance of being
+    /// a mine when a probability is given
+    pub fn as_span(
+        &self,
+        sub_line: usize,
+        probability: Option<f64>,
+        theme: &Theme,
+        glyphs: &Glyphs,
+    ) -> Vec<Span<'_>> {
         let mut line = match sub_line {
-            0 => {
-                let span = Span::raw("▗▄▖");
-                if self.tile_state() == TileState::Visible {
-                    if self.is_mine() {
-                        vec![span.red()]
-                    } else if self.bombs_near() == 0 {
-                        vec![Span::raw("   ")]
-                    } else {
-                        vec![span.dark_gray()]
-                    }
-                } else {
-                    vec![span]
-                }
-            }
+            0 => vec![self.edge_span(glyphs.top, theme)],
             1 => match self.tile_state() {
                 TileState::Hidden => {
-                    vec![Span::raw("▐█▌")]
+                    vec![Span::raw(glyphs.hidden)]
                 }
                 TileState::Question => {
                     vec![
-                        Span::raw("▐"),
-                        Span::raw("?").blue().bold().on_white(),
-                        Span::raw("▌"),
+                        Span::raw(glyphs.left),
+                        Span::raw(glyphs.question)
+                            .fg(theme.question)
+                            .bold()
+                            .bg(theme.marker_background),
+                        Span::raw(glyphs.right),
                     ]
                 }
                 TileState::Marked => {
                     vec![
-                        Span::raw("▐"),
-                        Span::raw("⚑").red().bold().on_white(),
-                        Span::raw("▌"),
+                        Span::raw(glyphs.left),
+                        Span::raw(glyphs.flag)
+                            .fg(theme.flag)
+                            .bold()
+                            .bg(theme.marker_background),
+                        Span::raw(glyphs.right),
                     ]
                 }
                 TileState::Visible => {
                     if self.is_mine() {
                         vec![
-                            Span::raw("▐").red(),
-                            Span::raw("*").bold().on_red(),
-                            Span::raw("▌").red(),
+                            Span::raw(glyphs.left).fg(theme.mine),
+                            Span::raw(glyphs.mine).bold().bg(theme.mine),
+                            Span::raw(glyphs.right).fg(theme.mine),
                         ]
                     } else if self.bombs_near() == 0 {
                         vec![Span::raw("   ")]
                     } else {
                         vec![
-                            Span::raw("▐").dark_gray(),
-                            num_as_span(self.bombs_near()).bold().on_dark_gray(),
-                            Span::raw("▌").dark_gray(),
+                            Span::raw(glyphs.left).fg(theme.revealed),
+                            num_as_span(self.bombs_near(), theme)
+                                .bold()
+                                .bg(theme.revealed),
+                            Span::raw(glyphs.right).fg(theme.revealed),
                         ]
                     }
                 }
             },
-            2 => {
-                let span = Span::raw("▝▀▘");
-                if self.tile_state() == TileState::Visible {
-                    if self.is_mine() {
-                        vec![span.red()]
-                    } else if self.bombs_near() == 0 {
-                        vec![Span::raw("   ")]
-                    } else {
-                        vec![span.dark_gray()]
-                    }
-                } else {
-                    vec![span]
-                }
-            }
+            2 => vec![self.edge_span(glyphs.bottom, theme)],
             _ => panic!(),
         };
         if self.fire {
             line = line
                 .into_iter()
                 .enumerate()
-                .map(|(index, span)| if index == 1 { span } else { span.on_red() })
+                .map(|(index, span)| {
+                    if index == 1 {
+                        span
+                    } else {
+                        span.bg(theme.fire)
+                    }
+                })
                 .collect();
         }
+        if let Some(probability) = probability {
+            if self.tile_state() != TileState::Visible {
+                // Keep the flag and question mark readable, only the tile itself is shaded
+                let style = probability_style(probability, theme);
+                line = line
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, span)| {
+                        if sub_line == 1 && index == 1 {
+                            span
+                        } else {
+                            span.patch_style(style)
+                        }
+                    })
+                    .collect();
+            }
+        }
+        if self.hint {
+            line = line.into_iter().map(|span| span.bg(theme.hint)).collect();
+        }
         line
     }
 }
 
-fn num_as_span(num: usize) -> Span<'static> {
-    assert!(num < 9);
-    match num {
-        1 => Span::raw("1").light_blue(),
-        2 => Span::raw("2").light_green(),
-        3 => Span::raw("3").light_red(),
-        4 => Span::raw("4").light_magenta(),
-        5 => Span::raw("5").light_yellow(),
-        6 => Span::raw("6").light_cyan(),
-        7 => Span::raw("7").black(),
-        8 => Span::raw("8").gray(),
-        _ => unreachable!(),
-    }
+/// Upper bounds of the probability ranges shown by the heatmap, the theme has a style for each
+const PROBABILITY_RANGES: [(f64, &str); 6] = [
+    (0., "Safe"),
+    (0.25, "Under 25%"),
+    (0.5, "Under 50%"),
+    (0.75, "Under 75%"),
+    (1., "Under 100%"),
+    (f64::INFINITY, "Mine"),
+];
+
+fn probability_style(probability: f64, theme: &Theme) -> Style {
+    let index = PROBABILITY_RANGES
+        .iter()
+        .position(|(bound, _)| {
+            if *bound <= 0. {
+                probability <= 0.
+            } else {
+                probability < *bound
+            }
+        })
+        .unwrap_or(PROBABILITY_RANGES.len() - 1);
+    theme.probabilities[index]
+}
+
+/// Explains the styles used by the heatmap
+pub fn heatmap_legend(theme: &Theme, glyphs: &Glyphs) -> Vec<Line<'static>> {
+    PROBABILITY_RANGES
+        .iter()
+        .zip(theme.probabilities)
+        .map(|((_, label), style)| {
+            Line::from(vec![
+                Span::styled(glyphs.hidden, style),
+                Span::raw(format!(" {label}")),
+            ])
+        })
+        .collect()
+}
+
+fn num_as_span(num: usize, theme: &Theme) -> Span<'static> {
+    assert!((1..9).contains(&num));
+    Span::raw(num.to_string()).fg(theme.number(num))
 }
 
 impl Default for Tile {
@@ -168,83 +342,11 @@ impl Default for Tile {
             state: TileState::Hidden,
             bombs_near: 0,
             fire: false,
+            hint: false,
         }
     }
 }
 
-pub fn do_around(
-    x: usize,
-    y: usize,
-    tiles: &mut [Vec<Tile>],
-    func: fn(&mut Tile) -> bool,
-) -> Vec<(usize, usize)> {
-    let mut matching: Vec<(usize, usize)> = Vec::new();
-    // above
-    if let Some(tile) = tiles
-        .get_mut(x)
-        .and_then(|x| x.get_mut(y.overflowing_sub(1).0))
-    {
-        if func(tile) {
-            matching.push((x, y - 1));
-        }
-    }
-    // bellow
-    if let Some(tile) = tiles.get_mut(x).and_then(|x| x.get_mut(y + 1)) {
-        if func(tile) {
-            matching.push((x, y + 1));
-        }
-    }
-    // left
-    if let Some(tile) = tiles
-        .get_mut(x.overflowing_sub(1).0)
-        .and_then(|x| x.get_mut(y))
-    {
-        if func(tile) {
-            matching.push((x - 1, y));
-        }
-    }
-    // right
-    if let Some(tile) = tiles.get_mut(x + 1).and_then(|x| x.get_mut(y)) {
-        if func(tile) {
-            matching.push((x + 1, y));
-        }
-    }
-    // above right
-    if let Some(tile) = tiles.get_mut(x + 1).and_then(|x| x.get_mut(y + 1)) {
-        if func(tile) {
-            matching.push((x + 1, y + 1));
-        }
-    }
-    // above left
-    if let Some(tile) = tiles
-        .get_mut(x.overflowing_sub(1).0)
-        .and_then(|x| x.get_mut(y + 1))
-    {
-        if func(tile) {
-            matching.push((x - 1, y + 1));
-        }
-    }
-    // bellow right
-    if let Some(tile) = tiles
-        .get_mut(x + 1)
-        .and_then(|x| x.get_mut(y.overflowing_sub(1).0))
-    {
-        if func(tile) {
-            matching.push((x + 1, y - 1));
-        }
-    }
-    // bellow left
-    if let Some(tile) = tiles
-        .get_mut(x.overflowing_sub(1).0)
-        .and_then(|x| x.get_mut(y.overflowing_sub(1).0))
-    {
-        if func(tile) {
-            matching.push((x - 1, y - 1));
-        }
-    }
-    matching
-}
-
 #[allow(clippy::comparison_chain)]
 #[allow(clippy::cast_possible_wrap)]
 fn circle_points(center: (usize, usize), x: isize, y: isize) -> Vec<(isize, isize)> {
@@ -298,33 +400,50 @@ fn circle_perimeter(center: (usize, usize), radius: f64) -> Vec<(isize, isize)>
     points
 }
 
-fn gen_tiles(difficulty: Difficulty, table_sizes: &[(usize, usize)]) -> Vec<Vec<Tile>> {
-    let ((max_x, max_y), mut mines) = match difficulty {
-        Difficulty::Easy => (table_sizes[0], 10),   // 80
-        Difficulty::Medium => (table_sizes[1], 40), // 252
-        Difficulty::Hard => (table_sizes[2], 99),   // 480
+/// Places the mines for the difficulty anywhere except on the `safe` tiles. If the safe area leaves
+/// too little room for the mines only the first safe tile is kept clear, and if even that is too
+/// much the mines go anywhere
+fn gen_tiles(
+    difficulty: Difficulty,
+    table_sizes: &[(usize, usize)],
+    rng: &mut impl Rng,
+    safe: &[(usize, usize)],
+) -> Grid<Tile> {
+    let (max_x, max_y) = match difficulty {
+        Difficulty::Easy => table_sizes[0],
+        Difficulty::Medium => table_sizes[1],
+        Difficulty::Hard => table_sizes[2],
+        Difficulty::Custom { width, height, .. } => (width, height),
     };
-    let mut tiles = vec![vec![Tile::default(); max_y]; max_x];
-
-    let mut rng = thread_rng();
-    while mines > 0 {
-        let x = rng.gen_range(0..max_x);
-        let y = rng.gen_range(0..max_y);
-        if tiles[x][y].is_mine {
-            continue;
-        }
-        tiles[x][y].is_mine = true;
-        // Handle numbers for all tiles near
-        do_around(x, y, &mut tiles, |tile| {
-            tile.bombs_near += 1;
-            false
-        });
-        mines -= 1;
+    let mines = difficulty.mines();
+    let mut tiles = Grid::new(max_x, max_y, Tile::default());
+
+    let all = || tiles.positions();
+    let mut candidates: Vec<(usize, usize)> = all().filter(|pos| !safe.contains(pos)).collect();
+    if candidates.len() < mines {
+        candidates = all().filter(|pos| safe.first() != Some(pos)).collect();
+    }
+    if candidates.len() < mines {
+        candidates = all().collect();
+    }
+
+    let mines = mines.min(candidates.len());
+    for index in rand::seq::index::sample(rng, candidates.len(), mines) {
+        let (x, y) = candidates[index];
+        place_mine(&mut tiles, x, y);
     }
     tiles
 }
 
-const fn check_compatible(board_size: (usize, usize), max_render_size: (u16, u16)) -> bool {
+fn place_mine(tiles: &mut Grid<Tile>, x: usize, y: usize) {
+    tiles[(x, y)].is_mine = true;
+    // Handle numbers for all tiles near
+    for pos in tiles.neighbours(x, y) {
+        tiles[pos].bombs_near += 1;
+    }
+}
+
+pub const fn check_compatible(board_size: (usize, usize), max_render_size: (u16, u16)) -> bool {
     let (x, y) = (board_size.0 * 3 + 12, board_size.1 * 3 + 2);
     let max_width = max_render_size.0 as usize;
     let max_height = max_render_size.1 as usize;
@@ -332,48 +451,131 @@ const fn check_compatible(board_size: (usize, usize), max_render_size: (u16, u16
     x <= max_width && y <= max_height
 }
 
+/// Most tiles a board may have, anything bigger could never fit in a terminal
+pub const MAX_TILES: usize = 1_000_000;
+
+/// Number of tiles on a board of this size, sizes read from files are checked with this before
+/// anything is allocated for them
+pub fn tile_count(width: usize, height: usize) -> Result<usize> {
+    width
+        .checked_mul(height)
+        .filter(|&tiles| tiles <= MAX_TILES)
+        .ok_or_else(|| anyhow!("A {width}x{height} board is too big"))
+}
+
+/// Sizes each preset difficulty can be played at, in the order they are tried. A size can also be
+/// turned on its side to fit the terminal
+const EASY_SIZES: [(usize, usize); 1] = [(10, 8)];
+const MEDIUM_SIZES: [(usize, usize); 4] = [(18, 14), (21, 12), (28, 9), (36, 7)];
+const HARD_SIZES: [(usize, usize); 6] = [(20, 24), (16, 30), (15, 32), (12, 40), (10, 48), (8, 60)];
+
+/// The first of the sizes that fits in the terminal either way round
+fn first_fit(sizes: &[(usize, usize)], max_render_size: (u16, u16)) -> Option<(usize, usize)> {
+    sizes.iter().find_map(|&(width, height)| {
+        if check_compatible((width, height), max_render_size) {
+            Some((width, height))
+        } else if check_compatible((height, width), max_render_size) {
+            Some((height, width))
+        } else {
+            None
+        }
+    })
+}
+
 fn get_compatible_sizes(max_render_size: (u16, u16)) -> [(usize, usize); 3] {
-    let mut sizes = [(0, 0), (0, 0), (0, 0)];
+    [
+        first_fit(&EASY_SIZES, max_render_size).expect("terminal is too small"),
+        first_fit(&MEDIUM_SIZES, max_render_size).unwrap_or_default(),
+        first_fit(&HARD_SIZES, max_render_size).unwrap_or_default(),
+    ]
+}
 
-    if check_compatible((10, 8), max_render_size) {
-        sizes[0] = (10, 8);
-    } else if check_compatible((8, 10), max_render_size) {
-        sizes[0] = (8, 10);
-    } else {
-        panic!("terminal is too small");
-    }
+/// How long no guess generation may search for a solvable layout before settling for any layout
+const NO_GUESS_TIME_LIMIT: Duration = Duration::from_secs(2);
+
+/// How much protection the first click of a game gets
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum FirstClickPolicy {
+    /// The first click can hit a mine like any other
+    Unprotected,
+    /// The first click is never a mine
+    SafeTile,
+    /// The first click always opens up an area
+    Opening,
+}
 
-    for pair in [(36, 7), (28, 9), (21, 12), (18, 14)].into_iter().rev() {
-        if check_compatible(pair, max_render_size) {
-            sizes[1] = pair;
-            break;
-        } else if check_compatible((pair.1, pair.0), max_render_size) {
-            sizes[1] = (pair.1, pair.0);
-            break;
+impl FirstClickPolicy {
+    pub const fn as_static_str(self) -> &'static str {
+        match self {
+            Self::Unprotected => "Unprotected",
+            Self::SafeTile => "Safe tile",
+            Self::Opening => "Opening",
         }
     }
 
-    for pair in [(8, 60), (10, 48), (12, 40), (15, 32), (16, 30), (20, 24)]
-        .into_iter()
-        .rev()
-    {
-        if check_compatible(pair, max_render_size) {
-            sizes[2] = pair;
-            break;
-        } else if check_compatible((pair.1, pair.0), max_render_size) {
-            sizes[2] = (pair.1, pair.0);
-            break;
+    pub fn parse(str: &str) -> Option<Self> {
+        [Self::Unprotected, Self::SafeTile, Self::Opening]
+            .into_iter()
+            .find(|policy| policy.as_static_str() == str)
+    }
+
+    pub const fn next(self) -> Self {
+        match self {
+            Self::Unprotected => Self::SafeTile,
+            Self::SafeTile => Self::Opening,
+            Self::Opening => Self::Unprotected,
         }
     }
+}
 
-    sizes
+/// Where a game is at, the instants record when the first move and the final move were made
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum GameState {
+    NotStarted,
+    Playing { started: Instant },
+    Won { started: Instant, ended: Instant },
+    Lost { started: Instant, ended: Instant },
 }
 
+impl GameState {
+    pub const fn is_over(self) -> bool {
+        matches!(self, Self::Won { .. } | Self::Lost { .. })
+    }
+
+    /// Time played so far, or in total once the game is over
+    pub fn elapsed(self) -> Duration {
+        match self {
+            Self::NotStarted => Duration::ZERO,
+            Self::Playing { started } => started.elapsed(),
+            Self::Won { started, ended } | Self::Lost { started, ended } => {
+                ended.duration_since(started)
+            }
+        }
+    }
+}
+
+#[allow(clippy::struct_excessive_bools)]
 pub struct Board {
     pub difficulty: Difficulty,
-    tiles: Vec<Vec<Tile>>,
-    game_over: Option<Instant>,
-    first_move: Option<Instant>,
+    /// Only accept layouts the solver can clear from the first click
+    pub no_guess: bool,
+    pub first_click: FirstClickPolicy,
+    /// Whether the current layout was verified to be solvable without guessing
+    guess_free: bool,
+    hints_used: usize,
+    seed: u64,
+    rng: StdRng,
+    tiles: Grid<Tile>,
+    state: GameState,
+    history: History,
+    /// Everything the player did this game, in order
+    actions: Vec<Action>,
+    /// Clicks that changed the board
+    effective_clicks: usize,
+    /// Whether the mine layout is final, it is settled by the first move or given up front
+    layout_settled: bool,
+    /// Whether the layout was imported, so it was known before the game started
+    imported: bool,
     game_over_pos: (usize, usize),
     game_over_state_counter: f64,
     pub max_render_size: (u16, u16),
@@ -381,43 +583,159 @@ pub struct Board {
 
 impl Board {
     pub fn new(difficulty: Difficulty, max_render_size: (u16, u16)) -> Self {
+        Self::with_seed(difficulty, max_render_size, thread_rng().gen())
+    }
+
+    /// Creates a board whose layout is entirely determined by the seed, the difficulty, the
+    /// render size and the position of the first click
+    pub fn with_seed(difficulty: Difficulty, max_render_size: (u16, u16), seed: u64) -> Self {
         let sizes = get_compatible_sizes(max_render_size);
+        let mut rng = StdRng::seed_from_u64(seed);
         Self {
             difficulty,
-            tiles: gen_tiles(difficulty, &sizes),
-            game_over: None,
-            first_move: None,
+            no_guess: false,
+            first_click: FirstClickPolicy::Opening,
+            guess_free: false,
+            hints_used: 0,
+            seed,
+            tiles: gen_tiles(difficulty, &sizes, &mut rng, &[]),
+            rng,
+            state: GameState::NotStarted,
+            history: History::default(),
+            actions: Vec::new(),
+            effective_clicks: 0,
+            layout_settled: false,
+            imported: false,
             game_over_pos: (0, 0),
             game_over_state_counter: 1.,
             max_render_size,
         }
     }
 
-    pub const fn first_move_time(&self) -> Option<Instant> {
-        self.first_move
+    /// Creates a board with a fixed mine layout, the first click leaves the layout alone
+    pub fn from_mines(
+        difficulty: Difficulty,
+        seed: u64,
+        mines: &Grid<bool>,
+        max_render_size: (u16, u16),
+    ) -> Self {
+        let mut board = Self::with_seed(difficulty, max_render_size, seed);
+        let mut tiles = Grid::new(mines.width(), mines.height(), Tile::default());
+        for (x, y) in mines.positions() {
+            if mines[(x, y)] {
+                place_mine(&mut tiles, x, y);
+            }
+        }
+        board.tiles = tiles;
+        board.layout_settled = true;
+        board
+    }
+
+    /// Creates a board from a shared layout, games on a layout known up front are practice
+    pub fn import(mines: &Grid<bool>, max_render_size: (u16, u16)) -> Result<Self> {
+        let (width, height) = mines.size();
+        if !check_compatible((width, height), max_render_size) {
+            bail!("A {width}x{height} board doesn't fit in the terminal");
+        }
+        let count = mines.iter().filter(|&&mine| mine).count();
+        if count == mines.len() {
+            bail!("The board has no safe tiles");
+        }
+        let difficulty = Difficulty::Custom {
+            width,
+            height,
+            mines: count,
+        };
+        let mut board = Self::from_mines(difficulty, 0, mines, max_render_size);
+        board.imported = true;
+        Ok(board)
+    }
+
+    /// Where the mines currently are
+    pub fn mine_layout(&self) -> Grid<bool> {
+        let mut mines = Grid::new(self.tiles.width(), self.tiles.height(), false);
+        for (x, y) in self.tiles.positions() {
+            mines[(x, y)] = self.tiles[(x, y)].is_mine;
+        }
+        mines
+    }
+
+    pub const fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub const fn is_guess_free(&self) -> bool {
+        self.guess_free
+    }
+
+    pub const fn state(&self) -> GameState {
+        self.state
     }
 
-    pub const fn last_move_time(&self) -> Option<Instant> {
-        self.game_over
+    /// Starts the clock if this is the first move of the game, undoing back to the start doesn't
+    /// let the next first move rearrange the mines again
+    fn start(&mut self) {
+        self.layout_settled = true;
+        if self.state == GameState::NotStarted {
+            self.state = GameState::Playing {
+                started: Instant::now(),
+            };
+        }
+    }
+
+    /// Ends the game, does nothing if it isn't being played
+    fn finish(&mut self, won: bool) {
+        if let GameState::Playing { started } = self.state {
+            let ended = Instant::now();
+            self.state = if won {
+                GameState::Won { started, ended }
+            } else {
+                GameState::Lost { started, ended }
+            };
+        }
+    }
+
+    /// A game is won once every tile without a mine has been revealed
+    fn all_safe_tiles_visible(&self) -> bool {
+        self.tiles
+            .iter()
+            .all(|tile| tile.is_mine() || tile.tile_state() == TileState::Visible)
     }
 
     pub fn set_max_board_size(&mut self, max_render_size: (u16, u16)) {
         self.max_render_size = max_render_size;
-        if self.first_move_time().is_none() {
-            self.tiles = gen_tiles(self.difficulty, &get_compatible_sizes(self.max_render_size));
+        if !self.layout_settled {
+            self.rng = StdRng::seed_from_u64(self.seed);
+            self.tiles = gen_tiles(
+                self.difficulty,
+                &get_compatible_sizes(self.max_render_size),
+                &mut self.rng,
+                &[],
+            );
         }
     }
 
-    pub fn to_widget(&self) -> Paragraph {
-        let max_y = self.tiles[0].len();
-        let max_x = self.tiles.len();
+    /// Renders the board, shading hidden tiles with the probabilities from the analysis if given
+    pub fn to_widget(
+        &self,
+        heatmap: Option<&Analysis>,
+        theme: &Theme,
+        glyphs: &Glyphs,
+    ) -> Paragraph<'_> {
+        let (max_x, max_y) = self.tiles.size();
 
         let mut text = Vec::with_capacity(max_y);
         for y in 0..max_y {
             for sub_line in 0..3 {
                 let mut span_vec = Vec::with_capacity(max_x);
                 for x in 0..max_x {
-                    span_vec.append(&mut self.tiles[x][y].as_span(sub_line));
+                    let probability = heatmap.and_then(|analysis| analysis.probability(x, y));
+                    span_vec.append(&mut self.tiles[(x, y)].as_span(
+                        sub_line,
+                        probability,
+                        theme,
+                        glyphs,
+                    ));
                 }
                 text.push(Line::from(span_vec));
             }
@@ -425,55 +743,199 @@ impl Board {
         Paragraph::new(text)
     }
 
+    pub fn actions(&self) -> &[Action] {
+        &self.actions
+    }
+
+    /// Replays an action the player made
+    pub fn apply(&mut self, action: Action) {
+        let Action { x, y, .. } = action;
+        match action.input {
+            Input::Reveal => self.left_click(x, y),
+            Input::Chord => self.do_control_click(x, y),
+            Input::Flag => self.right_click(x, y),
+            Input::Question => self.middle_click(x, y),
+            Input::Undo => {
+                self.undo();
+            }
+            Input::Redo => {
+                self.redo();
+            }
+        }
+    }
+
+    /// Finishes recording a move in the history, counting it as an effective click if it changed
+    /// anything
+    fn end_move(&mut self) {
+        if self.history.end(self.state) {
+            self.effective_clicks += 1;
+        }
+    }
+
+    /// How the game went so far, see [`Metrics`]
+    pub fn metrics(&self) -> Metrics {
+        Metrics {
+            bbbv: metrics::bbbv(&self.mine_layout()),
+            clicks: self
+                .actions
+                .iter()
+                .filter(|action| !matches!(action.input, Input::Undo | Input::Redo))
+                .count(),
+            effective_clicks: self.effective_clicks,
+        }
+    }
+
+    /// Adds an action to the record of the game, timed from the first move
+    fn record(&mut self, input: Input, x: usize, y: usize) {
+        let time = match self.state {
+            GameState::NotStarted => Duration::ZERO,
+            GameState::Playing { started }
+            | GameState::Won { started, .. }
+            | GameState::Lost { started, .. } => started.elapsed(),
+        };
+        self.actions.push(Action { time, input, x, y });
+    }
+
     pub fn left_click(&mut self, x: usize, y: usize) {
-        if self.game_over.is_some() {
+        if !self.state.is_over() {
+            self.record(Input::Reveal, x, y);
+        }
+        self.history.begin(self.state);
+        self.reveal(x, y);
+        self.end_move();
+    }
+
+    fn reveal(&mut self, x: usize, y: usize) {
+        if self.state.is_over() {
             return;
         }
-        let mut tile = self.tiles.get_mut(x).and_then(|x| x.get_mut(y));
+        if !self.layout_settled && self.tiles.contains(x, y) {
+            self.place_mines_for_first_click(x, y);
+        }
+        self.start();
 
-        while self.first_move.is_none()
-            && tile
-                .as_ref()
-                .map_or(false, |tile| tile.is_mine() || tile.bombs_near() > 0)
-        {
-            self.tiles = gen_tiles(self.difficulty, &get_compatible_sizes(self.max_render_size));
-            tile = self.tiles.get_mut(x).and_then(|x| x.get_mut(y));
+        let Some(tile) = self.tiles.get(x, y) else {
+            return;
+        };
+        if tile.tile_state() == TileState::Marked || tile.tile_state() == TileState::Visible {
+            return;
+        }
+        let (mine, flood) = (tile.is_mine(), tile.bombs_near() == 0);
+        self.set_tile_state(x, y, TileState::Visible);
+        if mine {
+            self.game_over_pos = (x, y);
+            self.finish(false);
+            return;
         }
+        if flood {
+            self.flood_fill(x, y);
+        }
+        if self.all_safe_tiles_visible() {
+            self.finish(true);
+            for (x, y) in self.tiles.positions() {
+                if self.tiles[(x, y)].is_mine() {
+                    self.set_tile_state(x, y, TileState::Marked);
+                }
+            }
+        }
+    }
+
+    /// Changes the state of a tile, recording it so it can be undone
+    fn set_tile_state(&mut self, x: usize, y: usize, state: TileState) {
+        let index = self.tiles.index(x, y);
+        let tile = &mut self.tiles[(x, y)];
+        self.history.record(index, tile.tile_state(), state);
+        tile.set_state(state);
+    }
 
-        if self.first_move.is_none() {
-            self.first_move = Some(Instant::now());
+    /// Whether the mines stay where they are, like on imported and replayed boards or once the
+    /// first click has been made
+    pub const fn layout_settled(&self) -> bool {
+        self.layout_settled
+    }
+
+    /// How much protection the next click gets, none once the layout is settled
+    pub const fn first_click_protection(&self) -> FirstClickPolicy {
+        if self.layout_settled {
+            FirstClickPolicy::Unprotected
+        } else if self.no_guess {
+            // No guess boards are only checked starting from an opening
+            FirstClickPolicy::Opening
+        } else {
+            self.first_click
         }
+    }
 
-        let mut flood = false;
-        if let Some(tile) = tile {
-            if tile.tile_state() == TileState::Marked {
+    /// Moves mines out of the way of the first click as the first click policy asks for, and if
+    /// no guess mode is enabled regenerates the layout until the solver can clear the board from
+    /// there. No guess generation falls back to the first layout tried once it runs out of time
+    fn place_mines_for_first_click(&mut self, x: usize, y: usize) {
+        let safe: Vec<(usize, usize)> = match self.first_click_protection() {
+            FirstClickPolicy::Unprotected => return,
+            FirstClickPolicy::SafeTile => vec![(x, y)],
+            FirstClickPolicy::Opening => std::iter::once((x, y))
+                .chain(self.tiles.neighbours(x, y))
+                .collect(),
+        };
+
+        // Every layout continues the seeded sequence that produced the initial layout, so the same
+        // seed and first click always settle on the same board
+        let sizes = get_compatible_sizes(self.max_render_size);
+        if safe.iter().any(|&pos| self.tiles[pos].is_mine()) {
+            self.tiles = gen_tiles(self.difficulty, &sizes, &mut self.rng, &safe);
+        }
+        self.guess_free = false;
+        if !self.no_guess {
+            return;
+        }
+
+        let deadline = Instant::now() + NO_GUESS_TIME_LIMIT;
+        let fallback = self.tiles.clone();
+        while !solver::is_solvable(&self.tiles, (x, y)) {
+            if Instant::now() >= deadline {
+                self.tiles = fallback;
                 return;
-            } else if tile.is_mine() {
-                tile.set_state(TileState::Visible);
-                self.game_over = Some(Instant::now());
-            } else if tile.state != TileState::Visible {
-                tile.set_state(TileState::Visible);
-                flood = tile.bombs_near() == 0;
             }
+            self.tiles = gen_tiles(self.difficulty, &sizes, &mut self.rng, &safe);
         }
-        if flood {
-            self.flood_fill(x, y);
+        self.guess_free = true;
+    }
+
+    pub const fn hints_used(&self) -> usize {
+        self.hints_used
+    }
+
+    /// Highlights a tile the visible board proves is safe, returns false if there is no such tile
+    pub fn show_hint(&mut self) -> bool {
+        if self.tiles.iter().any(|tile| tile.hint) {
+            return true;
         }
-        if self.game_over.is_some() {
-            self.game_over_pos = (x, y);
+        let analysis = solver::analyse(&View::from_board(self));
+        if let Some(&(x, y)) = analysis.safe.first() {
+            self.tiles[(x, y)].hint = true;
+            self.hints_used += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn clear_hint(&mut self) {
+        for tile in self.tiles.iter_mut() {
+            tile.hint = false;
         }
     }
 
     pub fn clear_fire(&mut self) {
-        for tile in self.tiles.iter_mut().flat_map(|vec| vec.iter_mut()) {
+        for tile in self.tiles.iter_mut() {
             tile.fire = false;
         }
     }
 
     fn do_game_over_animation_tile(&mut self, x: usize, y: usize) -> bool {
-        if let Some(tile) = self.tiles.get_mut(x).and_then(|col| col.get_mut(y)) {
+        if let Some(tile) = self.tiles.get_mut(x, y) {
             if tile.is_mine() {
-                tile.set_state(TileState::Visible);
+                self.set_tile_state(x, y, TileState::Visible);
             } else {
                 tile.fire = true;
             }
@@ -485,7 +947,7 @@ impl Board {
 
     #[allow(clippy::cast_sign_loss)]
     pub fn do_game_over_animation(&mut self) {
-        assert!(self.game_over.is_some());
+        assert!(matches!(self.state, GameState::Lost { .. }));
         self.clear_fire();
 
         let mut updated = false;
@@ -501,9 +963,9 @@ impl Board {
             }
         }
         if !updated {
-            for tile in self.tiles.iter_mut().flat_map(|vec| vec.iter_mut()) {
-                if tile.is_mine() {
-                    tile.set_state(TileState::Visible);
+            for (x, y) in self.tiles.positions() {
+                if self.tiles[(x, y)].is_mine() {
+                    self.set_tile_state(x, y, TileState::Visible);
                 }
             }
         }
@@ -511,140 +973,554 @@ impl Board {
     }
 
     pub fn do_control_click(&mut self, x: usize, y: usize) {
-        if self.game_over.is_some() {
+        if !self.state.is_over() {
+            self.record(Input::Chord, x, y);
+        }
+        self.history.begin(self.state);
+        self.chord(x, y);
+        self.end_move();
+    }
+
+    /// Reveals the tiles around a tile once all of its mines are flagged, and keeps going from
+    /// every tile that was revealed
+    fn chord(&mut self, x: usize, y: usize) {
+        if self.state.is_over() {
             return;
         }
         let mut tiles_to_left_click = Vec::new();
-        let tile = self.tiles.get(x).and_then(|x| x.get(y));
-        if tile.is_some() {
-            let num_around = tile.unwrap().bombs_near();
-            let marked_around = do_around(x, y, &mut self.tiles, |tile| {
-                tile.tile_state() == TileState::Marked
-            })
-            .len();
+        if let Some(tile) = self.tiles.get(x, y) {
+            let num_around = tile.bombs_near();
+            let marked_around = self
+                .tiles
+                .count_neighbours(x, y, |tile| tile.tile_state() == TileState::Marked);
             if num_around == marked_around {
-                tiles_to_left_click.append(&mut do_around(x, y, &mut self.tiles, |tile| {
+                tiles_to_left_click = self.tiles.neighbours_where(x, y, |tile| {
                     tile.tile_state() != TileState::Marked
                         && tile.tile_state() != TileState::Visible
-                }));
+                });
             }
         }
         for (x, y) in tiles_to_left_click {
-            self.left_click(x, y);
-            self.do_control_click(x, y);
+            self.reveal(x, y);
+            self.chord(x, y);
         }
     }
 
     pub fn right_click(&mut self, x: usize, y: usize) {
-        if self.game_over.is_some() {
+        if self.state.is_over() {
             return;
         }
-        let tile = self.tiles.get_mut(x).and_then(|x| x.get_mut(y));
-        if let Some(tile) = tile {
-            if self.first_move.is_none() {
-                self.first_move = Some(Instant::now());
-            }
-            if tile.tile_state() == TileState::Visible {
-            } else if tile.tile_state() == TileState::Marked {
-                tile.set_state(TileState::Hidden);
-            } else {
-                tile.set_state(TileState::Marked);
-            }
-        }
-        if self.check_all_mine_state(TileState::Marked) {
-            self.game_over = Some(Instant::now());
-            for tile in self.tiles.iter_mut().flat_map(|vec| vec.iter_mut()) {
-                if !tile.is_mine() {
-                    tile.set_state(TileState::Visible);
-                }
-            }
+        if !self.tiles.contains(x, y) {
+            return;
         }
-    }
-
-    pub fn check_all_mine_state(&self, state: TileState) -> bool {
-        self.tiles
-            .iter()
-            .flat_map(|vec| vec.iter())
-            .filter(|tile| tile.is_mine())
-            .all(|tile| tile.tile_state() == state)
+        self.record(Input::Flag, x, y);
+        self.history.begin(self.state);
+        self.start();
+        let state = match self.tiles[(x, y)].tile_state() {
+            TileState::Visible => TileState::Visible,
+            TileState::Marked => TileState::Hidden,
+            _ => TileState::Marked,
+        };
+        self.set_tile_state(x, y, state);
+        self.end_move();
     }
 
     pub fn middle_click(&mut self, x: usize, y: usize) {
-        if self.game_over.is_some() {
+        if self.state.is_over() {
             return;
         }
-        let tile = self.tiles.get_mut(x).and_then(|x| x.get_mut(y));
-        if let Some(tile) = tile {
-            if self.first_move.is_none() {
-                self.first_move = Some(Instant::now());
-            }
-            if tile.tile_state() == TileState::Visible {
-            } else if tile.tile_state() == TileState::Question {
-                tile.set_state(TileState::Hidden);
-            } else {
-                tile.set_state(TileState::Question);
-            }
+        if !self.tiles.contains(x, y) {
+            return;
         }
+        self.record(Input::Question, x, y);
+        self.history.begin(self.state);
+        self.start();
+        let state = match self.tiles[(x, y)].tile_state() {
+            TileState::Visible => TileState::Visible,
+            TileState::Question => TileState::Hidden,
+            _ => TileState::Question,
+        };
+        self.set_tile_state(x, y, state);
+        self.end_move();
     }
 
     pub fn flood_fill(&mut self, x: usize, y: usize) {
         let mut around = vec![(x, y)];
         while let Some((x, y)) = around.pop() {
-            around.append(&mut do_around(x, y, &mut self.tiles, |tile| {
-                if tile.state == TileState::Visible {
-                    false
-                } else if tile.bombs_near() == 0 && !tile.is_mine() {
-                    tile.set_state(TileState::Visible);
-                    true
-                } else {
-                    tile.set_state(TileState::Visible);
-                    false
+            for (x, y) in self.tiles.neighbours(x, y) {
+                let tile = &self.tiles[(x, y)];
+                if tile.tile_state() == TileState::Visible {
+                    continue;
                 }
-            }));
+                if tile.bombs_near() == 0 && !tile.is_mine() {
+                    around.push((x, y));
+                }
+                self.set_tile_state(x, y, TileState::Visible);
+            }
+        }
+    }
+
+    /// Takes back the last move, the losing move included. A won game stays won
+    pub fn undo(&mut self) -> bool {
+        if matches!(self.state, GameState::Won { .. }) {
+            return false;
+        }
+        self.history.undo().is_some_and(|restore| {
+            self.record(Input::Undo, 0, 0);
+            self.restore(restore);
+            true
+        })
+    }
+
+    pub fn redo(&mut self) -> bool {
+        self.history.redo().is_some_and(|restore| {
+            self.record(Input::Redo, 0, 0);
+            self.restore(restore);
+            true
+        })
+    }
+
+    fn restore(&mut self, restore: Restore) {
+        for (index, state) in restore.tiles {
+            let pos = self.tiles.coords(index);
+            self.tiles[pos].set_state(state);
         }
+        self.state = restore.state;
+        self.game_over_state_counter = 1.;
+        self.clear_fire();
+    }
+
+    /// Whether a move was ever undone this game
+    pub const fn undo_used(&self) -> bool {
+        self.history.used()
     }
 
-    pub fn get_board_size(&self) -> (usize, usize) {
-        let x = self.tiles.len();
-        let y = self.tiles[0].len();
-        (x, y)
+    /// Whether the game is practice and isn't scored, because a move was undone or the layout was
+    /// known up front
+    pub const fn is_practice(&self) -> bool {
+        self.undo_used() || self.imported
+    }
+
+    pub fn tile(&self, x: usize, y: usize) -> Option<&Tile> {
+        self.tiles.get(x, y)
+    }
+
+    /// Total number of mines on the board, this is public knowledge so the solver may use it
+    pub fn mine_count(&self) -> usize {
+        self.tiles.iter().filter(|tile| tile.is_mine()).count()
+    }
+
+    pub const fn get_board_size(&self) -> (usize, usize) {
+        self.tiles.size()
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
-pub struct Score {
-    difficulty: Difficulty,
-    time: Duration,
+/// Version of the save file format, saves written by other versions are rejected
+const SAVE_VERSION: u32 = 1;
+const SAVE_HEADER: &str = "termsweeper save";
+
+/// How each combination of mine and tile state is written in a save file
+const SAVE_TILES: [(bool, TileState, char); 8] = [
+    (false, TileState::Hidden, '.'),
+    (false, TileState::Question, '?'),
+    (false, TileState::Marked, 'f'),
+    (false, TileState::Visible, 'o'),
+    (true, TileState::Hidden, '*'),
+    (true, TileState::Question, '!'),
+    (true, TileState::Marked, 'F'),
+    (true, TileState::Visible, 'X'),
+];
+
+/// An unfinished game read back from a save file, the clock restarts once it is resumed
+pub struct SavedGame {
+    pub board: Board,
+    pub elapsed: Duration,
+    /// Whether the heatmap was used before the game was saved
+    pub heatmap_used: bool,
 }
 
-impl Score {
-    pub const fn new(difficulty: Difficulty, time: Duration) -> Self {
-        Self { difficulty, time }
+impl SavedGame {
+    /// Writes out a game that is being played, returns `None` for games that aren't worth
+    /// keeping because they haven't started or are already over
+    pub fn serialise(board: &Board, heatmap_used: bool) -> Option<String> {
+        let GameState::Playing { started } = board.state else {
+            return None;
+        };
+        let mut lines = vec![format!("{SAVE_HEADER} {SAVE_VERSION}")];
+        for (key, value) in [
+            ("difficulty", board.difficulty.as_string()),
+            ("seed", board.seed.to_string()),
+            ("no guess", board.no_guess.to_string()),
+            ("guess free", board.guess_free.to_string()),
+            ("first click", board.first_click.as_static_str().to_string()),
+            ("hints", board.hints_used.to_string()),
+            ("effective clicks", board.effective_clicks.to_string()),
+            ("undone", board.undo_used().to_string()),
+            ("imported", board.imported.to_string()),
+            ("heatmap", heatmap_used.to_string()),
+            ("elapsed", started.elapsed().as_millis().to_string()),
+        ] {
+            lines.push(format!("{key}: {value}"));
+        }
+        lines.push(String::from("tiles:"));
+        for y in 0..board.tiles.height() {
+            let row = (0..board.tiles.width())
+                .map(|x| {
+                    let tile = &board.tiles[(x, y)];
+                    let (_, _, char) = SAVE_TILES
+                        .iter()
+                        .find(|(mine, state, _)| *mine == tile.is_mine && *state == tile.state)
+                        .unwrap();
+                    *char
+                })
+                .collect();
+            lines.push(row);
+        }
+        lines.push(String::from("actions:"));
+        lines.extend(board.actions.iter().map(Action::as_string));
+        lines.push(String::new());
+        Some(lines.join("\n"))
     }
 
-    pub fn as_string(&self) -> String {
-        format!(
-            "{}: {}",
-            self.difficulty.as_static_str(),
-            self.time.as_secs()
-        )
+    pub fn parse(save: &str, max_render_size: (u16, u16)) -> Result<Self> {
+        let mut lines = save.lines();
+        let version = lines
+            .next()
+            .and_then(|header| header.strip_prefix(SAVE_HEADER))
+            .ok_or_else(|| anyhow!("Not a save file"))?;
+        if version.trim() != SAVE_VERSION.to_string() {
+            bail!("Save file version {} isn't supported", version.trim());
+        }
+
+        let mut values = HashMap::new();
+        for line in lines.by_ref() {
+            if line == "tiles:" {
+                break;
+            }
+            let (key, value) = line
+                .split_once(": ")
+                .ok_or_else(|| anyhow!("Malformed line '{line}'"))?;
+            values.insert(key, value);
+        }
+        let value = |key: &str| {
+            values
+                .get(key)
+                .copied()
+                .ok_or_else(|| anyhow!("Missing '{key}'"))
+        };
+        let flag = |key: &str| -> Result<bool> { Ok(value(key)?.parse()?) };
+
+        let difficulty =
+            Difficulty::parse(value("difficulty")?).ok_or_else(|| anyhow!("Invalid difficulty"))?;
+        // The difficulty is only trusted once the tiles agree with it, a board is built for it
+        // after that
+        let tiles = parse_save_tiles(lines.by_ref().take_while(|line| *line != "actions:"))?;
+        let size = tiles.size();
+        if !difficulty.allows_size(size) {
+            bail!("The tiles don't match the difficulty");
+        }
+        if tiles.iter().filter(|tile| tile.is_mine).count() != difficulty.mines() {
+            bail!("The save has the wrong number of mines");
+        }
+        if !check_compatible(size, max_render_size) {
+            bail!("The saved board doesn't fit in the terminal");
+        }
+        let seed = value("seed")?.parse()?;
+        let mut board = Board::with_seed(difficulty, max_render_size, seed);
+        board.layout_settled = true;
+        board.tiles = tiles;
+        board.actions = lines
+            .filter(|line| !line.is_empty())
+            .map(|line| Action::parse(line).ok_or_else(|| anyhow!("Invalid action '{line}'")))
+            .collect::<Result<_>>()?;
+        board.no_guess = flag("no guess")?;
+        board.guess_free = flag("guess free")?;
+        board.first_click = FirstClickPolicy::parse(value("first click")?)
+            .ok_or_else(|| anyhow!("Invalid first click policy"))?;
+        board.hints_used = value("hints")?.parse()?;
+        board.effective_clicks = value("effective clicks")?.parse()?;
+        if flag("undone")? {
+            board.history.mark_used();
+        }
+        board.imported = flag("imported")?;
+        Ok(Self {
+            board,
+            elapsed: Duration::from_millis(value("elapsed")?.parse()?),
+            heatmap_used: flag("heatmap")?,
+        })
     }
 
-    pub const fn time(&self) -> Duration {
-        self.time
+    /// Puts the game back in play with the clock showing the time played before it was saved
+    pub fn resume(self) -> Board {
+        let mut board = self.board;
+        let now = Instant::now();
+        board.state = GameState::Playing {
+            started: now.checked_sub(self.elapsed).unwrap_or(now),
+        };
+        board
+    }
+}
+
+fn parse_save_tiles<'a>(lines: impl Iterator<Item = &'a str>) -> Result<Grid<Tile>> {
+    let rows: Vec<&str> = lines.filter(|line| !line.is_empty()).collect();
+    let width = rows.first().map_or(0, |row| row.chars().count());
+    if width == 0 {
+        bail!("The save has no tiles");
     }
+    tile_count(width, rows.len())?;
+    let mut tiles = Grid::new(width, rows.len(), Tile::default());
+    for (y, row) in rows.iter().enumerate() {
+        if row.chars().count() != width {
+            bail!("Row {} of the tiles has the wrong length", y + 1);
+        }
+        for (x, char) in row.chars().enumerate() {
+            let &(mine, state, _) = SAVE_TILES
+                .iter()
+                .find(|(_, _, tile_char)| *tile_char == char)
+                .ok_or_else(|| anyhow!("Unknown tile '{char}'"))?;
+            if mine {
+                place_mine(&mut tiles, x, y);
+            }
+            tiles[(x, y)].state = state;
+        }
+    }
+    Ok(tiles)
+}
 
-    pub const fn difficulty(&self) -> Difficulty {
-        self.difficulty
+/// Writes a time as seconds with millisecond precision, like `12.345`
+pub fn format_seconds(time: Duration) -> String {
+    format!("{}.{:03}", time.as_secs(), time.subsec_millis())
+}
+
+/// Reads a time written by `format_seconds`, whole seconds and extra digits are accepted too
+pub fn parse_seconds(str: &str) -> Option<Duration> {
+    let (secs, fraction) = str.split_once('.').unwrap_or((str, ""));
+    if !fraction.chars().all(|char| char.is_ascii_digit()) {
+        return None;
     }
+    let millis: String = fraction.chars().chain("000".chars()).take(3).collect();
+    Some(Duration::from_secs(secs.parse().ok()?) + Duration::from_millis(millis.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    pub fn as_list_item(&self) -> ListItem {
-        let difficulty = self.difficulty.as_span();
-        let mid = Span::raw(": ");
-        let time = Span::raw(self.time().as_secs().to_string()).blue().bold();
-        let text = Line::default()
-            .spans(vec![difficulty, mid, time, Span::raw("s")])
-            .centered();
-        ListItem::new(text)
+    #[test]
+    fn no_guess_generation_stops_at_the_time_limit() {
+        // Half the board is mines, so almost every layout needs a guess and the opening is rare
+        let difficulty = Difficulty::Custom {
+            width: 10,
+            height: 10,
+            mines: 50,
+        };
+        let mut board = Board::with_seed(difficulty, (200, 100), 1);
+        board.no_guess = true;
+        let start = Instant::now();
+        board.place_mines_for_first_click(5, 5);
+        assert!(start.elapsed() < NO_GUESS_TIME_LIMIT + Duration::from_secs(1));
+    }
+
+    /// A save of an easy game one click in
+    fn easy_save() -> String {
+        let mut board = Board::with_seed(Difficulty::Easy, (200, 100), 1);
+        board.left_click(0, 0);
+        SavedGame::serialise(&board, false).unwrap()
+    }
+
+    #[test]
+    fn save_round_trips() {
+        let save = easy_save();
+        let saved = SavedGame::parse(&save, (200, 100)).unwrap();
+        assert_eq!(saved.board.get_board_size(), (10, 8));
+        assert_eq!(saved.board.mine_count(), 10);
+        assert_eq!(SavedGame::serialise(&saved.resume(), false).unwrap(), save);
+    }
+
+    fn parse_error(save: &str, max_render_size: (u16, u16)) -> String {
+        SavedGame::parse(save, max_render_size)
+            .err()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn save_must_match_its_difficulty() {
+        let save = easy_save().replace("difficulty: Easy", "difficulty: Medium");
+        assert_eq!(
+            parse_error(&save, (200, 100)),
+            "The tiles don't match the difficulty"
+        );
+        let save = easy_save().replace("difficulty: Easy", "difficulty: Custom 10x8/12");
+        assert_eq!(
+            parse_error(&save, (200, 100)),
+            "The save has the wrong number of mines"
+        );
+    }
+
+    #[test]
+    fn oversized_saves_are_rejected_before_allocating() {
+        let save = easy_save().replace("difficulty: Easy", "difficulty: Custom 30000x30000/1");
+        assert_eq!(
+            parse_error(&save, (200, 100)),
+            "The tiles don't match the difficulty"
+        );
+        let save = easy_save().replace(
+            "difficulty: Easy",
+            &format!("difficulty: Custom {}x2/1", usize::MAX),
+        );
+        assert_eq!(
+            parse_error(&save, (200, 100)),
+            "The tiles don't match the difficulty"
+        );
+
+        // Tiles that would make a huge board are caught before the grid is made
+        let save = easy_save();
+        let (header, _) = save.split_once("tiles:\n").unwrap();
+        let row = ".".repeat(2000);
+        let save = format!(
+            "{header}tiles:\n{}actions:\n",
+            format!("{row}\n").repeat(600)
+        );
+        assert_eq!(
+            parse_error(&save, (200, 100)),
+            "A 2000x600 board is too big"
+        );
+    }
+
+    #[test]
+    fn huge_custom_difficulties_are_clamped() {
+        let difficulty = Difficulty::Custom {
+            width: usize::MAX,
+            height: usize::MAX,
+            mines: usize::MAX,
+        };
+        let Difficulty::Custom {
+            width,
+            height,
+            mines,
+        } = difficulty.clamp_to((200, 100))
+        else {
+            unreachable!();
+        };
+        assert!(check_compatible((width, height), (200, 100)));
+        assert_eq!(mines, width * height - 1);
+    }
+
+    #[test]
+    fn save_must_have_every_mine() {
+        let save = easy_save();
+        let (header, rest) = save.split_once("tiles:\n").unwrap();
+        let rest = rest.replacen('*', ".", 1);
+        assert_eq!(
+            parse_error(&format!("{header}tiles:\n{rest}"), (200, 100)),
+            "The save has the wrong number of mines"
+        );
+    }
+
+    #[test]
+    fn save_must_fit_in_the_terminal() {
+        // Easy fits when turned on its side, but the save keeps the way round it was played
+        assert_eq!(
+            parse_error(&easy_save(), (40, 40)),
+            "The saved board doesn't fit in the terminal"
+        );
+    }
+
+    #[test]
+    fn clicks_that_change_nothing_keep_the_redo() {
+        let mut mines = Grid::new(4, 4, false);
+        mines[(3, 0)] = true;
+        mines[(3, 3)] = true;
+        let mut board = Board::import(&mines, (200, 100)).unwrap();
+        board.left_click(0, 0);
+        board.right_click(3, 0);
+        assert!(board.undo());
+        // Revealed tiles can't be revealed or flagged again
+        board.left_click(0, 0);
+        board.right_click(1, 1);
+        board.do_control_click(0, 0);
+        assert!(board.redo());
+        assert!(board.tiles[(3, 0)].tile_state() == TileState::Marked);
+
+        // A move that does change something replaces what was undone
+        assert!(board.undo());
+        board.right_click(3, 3);
+        assert!(!board.redo());
+    }
+
+    #[test]
+    fn imported_boards_are_practice_without_undo() {
+        let mut mines = Grid::new(9, 9, false);
+        mines[(8, 8)] = true;
+        let mut board = Board::import(&mines, (200, 100)).unwrap();
+        assert!(board.is_practice());
+        assert!(!board.undo_used());
+
+        board.right_click(8, 8);
+        let save = SavedGame::serialise(&board, false).unwrap();
+        let board = SavedGame::parse(&save, (200, 100)).unwrap().board;
+        assert!(board.is_practice());
+        assert!(!board.undo_used());
+        // The mines are already placed, so the first click is a guess like any other
+        assert!(board.first_click_protection() == FirstClickPolicy::Unprotected);
+    }
+
+    /// Times the board operations on a 1000x1000 board, run it with
+    /// `cargo test --release bench -- --ignored --nocapture`
+    #[test]
+    #[ignore = "benchmark"]
+    fn bench_large_board() {
+        let difficulty = Difficulty::Custom {
+            width: 1000,
+            height: 1000,
+            mines: 1000,
+        };
+        let render_size = (u16::MAX, u16::MAX);
+        let time = |name: &str, start: Instant| println!("{name}: {:?}", start.elapsed());
+
+        let sizes = get_compatible_sizes(render_size);
+        let start = Instant::now();
+        let tiles = gen_tiles(difficulty, &sizes, &mut StdRng::seed_from_u64(1), &[]);
+        time("gen_tiles", start);
+        assert_eq!(tiles.iter().filter(|tile| tile.is_mine()).count(), 1000);
+
+        // So few mines leaves one opening covering nearly the whole board
+        let mut board = Board::with_seed(difficulty, render_size, 1);
+        let start = Instant::now();
+        board.left_click(500, 500);
+        time("flood_fill", start);
+        let visible = |board: &Board| {
+            board
+                .tiles
+                .iter()
+                .filter(|tile| tile.tile_state() == TileState::Visible)
+                .count()
+        };
+        assert!(visible(&board) > 900_000);
+
+        // Flag every mine, then chord every number to uncover whatever the opening left covered
+        let mines: Vec<(usize, usize)> = board
+            .tiles
+            .positions()
+            .filter(|&pos| board.tiles[pos].is_mine())
+            .collect();
+        for (x, y) in mines {
+            board.right_click(x, y);
+        }
+        let numbers: Vec<(usize, usize)> = board
+            .tiles
+            .positions()
+            .filter(|&pos| {
+                let number = &board.tiles[pos];
+                number.tile_state() == TileState::Visible && number.bombs_near() > 0
+            })
+            .collect();
+        let start = Instant::now();
+        for (x, y) in numbers {
+            board.do_control_click(x, y);
+        }
+        time("do_control_click", start);
+        assert_eq!(visible(&board), 1_000_000 - 1000);
     }
 }